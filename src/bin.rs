@@ -56,6 +56,7 @@ fn main() -> Result<(), String> {
 
     println!("{}", b);
     let mut history = vec![];
+    let mut positions = PositionHistory::default();
 
     loop {
         let mut s = input(">>> ");
@@ -91,7 +92,7 @@ fn main() -> Result<(), String> {
             }
         };
 
-        match b.play_move(m) {
+        match b.play_move_with(m, &mut positions) {
             GameResult::Continuing(next_board) => {
                 b = next_board;
                 println!("{}", b);
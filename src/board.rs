@@ -1,9 +1,11 @@
 use super::*;
 use alloc::{
+    collections::BTreeMap,
     string::{String, ToString},
     vec::Vec,
 };
 use core::{cmp::Ordering};
+use dashmap::DashMap;
 
 pub struct BoardBuilder {
     board: Board,
@@ -113,6 +115,21 @@ impl BoardBuilder {
         self
     }
 
+    pub fn set_halfmove_clock(mut self, halfmove_clock: u8) -> Self {
+        self.board.halfmoves = halfmove_clock;
+        self
+    }
+
+    pub fn set_fullmove_number(mut self, fullmove_number: u16) -> Self {
+        self.board.fullmoves = fullmove_number;
+        self
+    }
+
+    pub fn set_piece_values(mut self, values: PieceValues) -> Self {
+        self.board = self.board.set_piece_values(values);
+        self
+    }
+
     pub fn build(self) -> Board {
         self.board
     }
@@ -169,6 +186,175 @@ impl CastlingRights {
     }
 }
 
+/// The side of the board a king castles towards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CastleSide {
+    KingSide,
+    QueenSide,
+}
+
+/// Whether a castle is legal, and if not, which condition it fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CastleLegality {
+    /// Every condition for castling is satisfied.
+    Legal,
+    /// The king has left its starting square at some point in the game.
+    KingMoved,
+    /// The rook on the respective side has moved or been captured.
+    RookMoved,
+    /// A piece stands between the king and the rook.
+    PathBlocked,
+    /// The king is currently in check.
+    InCheck,
+    /// The square the king passes over is threatened by an enemy piece.
+    ThroughCheck,
+    /// The square the king lands on is threatened by an enemy piece.
+    IntoCheck,
+}
+
+impl CastleLegality {
+    /// Is the castle legal?
+    #[inline]
+    pub fn is_legal(&self) -> bool {
+        *self == Self::Legal
+    }
+}
+
+/// Why a move can't be played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IllegalMoveReason {
+    /// The game already ended before the move.
+    GameOver,
+    /// There is no piece on the square the move starts from.
+    EmptySquare,
+    /// The piece being moved belongs to the other player.
+    NotYourPiece,
+    /// The piece can't move to the target square, for example because
+    /// it doesn't move that way or another piece is in the way.
+    IllegalMovement,
+    /// The move is a promotion, but not a pawn reaching the last rank
+    /// and becoming a knight, bishop, rook or queen.
+    InvalidPromotion,
+    /// The move would leave the player's own king in check.
+    LeavesKingInCheck,
+    /// The move is a castle, and castling is not legal for the given reason.
+    Castle(CastleLegality),
+}
+
+/// A move written both for machines and for people.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoveNotation {
+    /// The move in UCI long algebraic notation, like `e2e4` or `a7a8q`.
+    pub uci: String,
+    /// The move in standard algebraic notation, like `e4` or `a8=Q+`.
+    pub san: String,
+}
+
+/// A move for `Board::play`, either as a `Move` or as text to read against
+/// the board it is played on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveInput {
+    Move(Move),
+    /// UCI like `e2e4`, standard algebraic notation like `e4`, or any of
+    /// the formats `Move::parse` reads, like `e2 to e4`.
+    Text(String),
+}
+
+impl MoveInput {
+    /// Find the move this input stands for when it's played on `board`.
+    ///
+    /// Text is read as UCI first, then as standard algebraic notation, and
+    /// last in the formats `Move::parse` reads.
+    pub fn resolve(&self, board: &Board) -> Result<Move, String> {
+        let text = match self {
+            Self::Move(m) => return Ok(*m),
+            Self::Text(text) => text.trim(),
+        };
+        board
+            .move_from_uci(text)
+            .or_else(|_| Move::from_san(text, board))
+            .or_else(|_| Move::try_from(String::from(text)))
+            .map_err(|_| format!("`{}` is not a move", text))
+    }
+}
+
+impl From<Move> for MoveInput {
+    fn from(m: Move) -> Self {
+        Self::Move(m)
+    }
+}
+
+impl From<&str> for MoveInput {
+    fn from(text: &str) -> Self {
+        Self::Text(String::from(text))
+    }
+}
+
+impl From<String> for MoveInput {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+/// What a move changed on a board, so `Board::revert` can take it back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveUndo {
+    m: Move,
+    // the piece that moved as it stood before the move, or the king for a
+    // castle, or None if the move didn't move anything
+    moved: Option<Piece>,
+    // the piece the move took, on the square it was taken on
+    captured: Option<Piece>,
+    en_passant: Option<Position>,
+    white_castling_rights: CastlingRights,
+    black_castling_rights: CastlingRights,
+    turn: Color,
+    halfmoves: u8,
+    fullmoves: u16,
+}
+
+/// The positions a game has been through since the last capture or pawn
+/// move, for spotting repetitions.
+///
+/// Boards don't remember how they were reached, so they stay small and
+/// cheap to copy during search. Games that should end on a threefold
+/// repetition keep one of these alongside the board, and play their moves
+/// with `Board::play_move_with`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PositionHistory {
+    // the Zobrist hashes of the positions before the current one, oldest first
+    hashes: Vec<u64>,
+}
+
+impl PositionHistory {
+    /// Get the number of times the position on `board` has been reached,
+    /// counting this time, if it was reached through this history.
+    ///
+    /// Positions are the same when they have the same pieces on the same
+    /// squares, the same player to move, the same castling rights and the
+    /// same usable en passant square.
+    pub fn repetitions(&self, board: &Board) -> usize {
+        let hash = board.zobrist();
+        self.hashes.iter().filter(|seen| **seen == hash).count() + 1
+    }
+
+    /// Has the position on `board` been reached three times?
+    #[inline]
+    pub fn is_threefold_repetition(&self, board: &Board) -> bool {
+        self.repetitions(board) >= 3
+    }
+
+    // remember that a move was played from `board` to `next`
+    fn record(&mut self, board: &Board, next: &Board) {
+        if next.halfmoves == 0 {
+            // nothing before a capture or pawn move can come up again
+            self.hashes.clear();
+        } else {
+            self.hashes.push(board.zobrist());
+        }
+    }
+}
+
 impl Default for Board {
     fn default() -> Self {
         BoardBuilder::default()
@@ -206,6 +392,9 @@ pub struct Board {
 
     turn: Color,
     halfmoves: u8,
+    fullmoves: u16,
+
+    piece_values: PieceValues,
 }
 impl Evaluate for Board {
     // Evaluate isn't directly a trait of board so we have to do this
@@ -219,6 +408,11 @@ impl Evaluate for Board {
         return self.is_stalemate()
     }
 
+    #[inline]
+    fn piece_values(&self) -> PieceValues {
+        self.piece_values
+    }
+
     #[inline]
     fn value_for(&self, ally_color: Color) -> f64 {
         let mut result = self.squares
@@ -248,10 +442,10 @@ impl Evaluate for Board {
         for square in &self.squares {
             if let Some(piece) = square.get_piece() {
                 if piece.get_color() == ally_color {
-                    ally_moves.push(piece.get_legal_moves(self).len() as f64)
+                    ally_moves.push(self.piece_mobility(piece.get_pos()) as f64)
                 }
                 else {
-                    enemy_moves.push(piece.get_legal_moves(self).len() as f64)
+                    enemy_moves.push(self.piece_mobility(piece.get_pos()) as f64)
                 }
             }
         }
@@ -261,18 +455,18 @@ impl Evaluate for Board {
     }
 
     #[inline]
-    fn naive_value_for(&self, ally_color: Color) -> f64 {
+    fn naive_value_with(&self, ally_color: Color, values: &PieceValues) -> f64 {
         let mut result = self.squares
             .iter()
             .map(|square| match square.get_piece() {
+                Some(Piece::King(..)) | None => 0.0,
                 Some(piece) => {
                     if piece.get_color() == ally_color {
-                        1.0
+                        values.value(piece)
                     } else {
-                        -1.0
+                        -values.value(piece)
                     }
                 }
-                None => 0.0,
             })
             .sum();
             //Naive engine will get excited at any check, rather than mates
@@ -304,7 +498,7 @@ impl Evaluate for Board {
     #[inline]
     fn closest_value_for(&self, ally_color: Color) -> f64 {
         let mut min_dist = 0.0;
-        let king: Position = self.get_king_pos(ally_color).unwrap();
+        let king: Position = self.king_square(ally_color).unwrap();
         for (_, square) in self.squares.iter().enumerate() {
             if let Some(piece) = square.get_piece() {             
                 if piece.get_color() != ally_color {
@@ -347,6 +541,101 @@ impl Evaluate for Board {
         result
     }
 
+    fn trapped_pieces_value_for(&self, ally_color: Color) -> f64 {
+        self.squares
+            .iter()
+            .filter_map(|square| square.get_piece())
+            .filter(|piece| matches!(piece, Piece::Knight(..) | Piece::Bishop(..)))
+            .filter(|piece| self.piece_mobility(piece.get_pos()) <= TRAPPED_MOBILITY)
+            .map(|piece| {
+                let row = piece.get_pos().get_row();
+                let in_enemy_half = match piece.get_color() {
+                    WHITE => row >= 4,
+                    BLACK => row <= 3,
+                };
+                let penalty = if in_enemy_half { 2.0 } else { 1.0 };
+                if piece.get_color() == ally_color {
+                    -penalty
+                } else {
+                    penalty
+                }
+            })
+            .sum()
+    }
+
+    fn material_imbalance_value_for(&self, ally_color: Color) -> f64 {
+        let mut counts = [[0; 4]; 2];
+        for piece in self.squares.iter().filter_map(|square| square.get_piece()) {
+            let kind = match piece {
+                Piece::Knight(..) => 0,
+                Piece::Bishop(..) => 1,
+                Piece::Rook(..) => 2,
+                Piece::Queen(..) => 3,
+                _ => continue,
+            };
+            counts[(piece.get_color() == ally_color) as usize][kind] += 1;
+        }
+        let [enemy, ally] =
+            counts.map(|[knights, bishops, rooks, queens]| imbalance(knights, bishops, rooks, queens));
+        ally - enemy
+    }
+
+    fn rook_behind_passer_value_for(&self, ally_color: Color) -> f64 {
+        let pieces: Vec<Piece> = self.squares.iter().filter_map(|square| square.get_piece()).collect();
+        let passers: Vec<(Position, Color)> = pieces
+            .iter()
+            .filter(|piece| self.is_passed_pawn(piece.get_pos()))
+            .map(|piece| (piece.get_pos(), piece.get_color()))
+            .collect();
+
+        let mut result = 0.0;
+        for rook in pieces.iter().filter(|piece| matches!(piece, Piece::Rook(..))) {
+            let pos = rook.get_pos();
+            for &(pawn, pawn_color) in &passers {
+                let behind = match pawn_color {
+                    WHITE => pos.get_row() < pawn.get_row(),
+                    BLACK => pos.get_row() > pawn.get_row(),
+                };
+                let open = (pos.get_row().min(pawn.get_row()) + 1..pos.get_row().max(pawn.get_row()))
+                    .all(|row| self.has_no_piece(Position::new(row, pos.get_col())));
+                if pos.get_col() == pawn.get_col() && behind && open {
+                    if rook.get_color() == ally_color {
+                        result += ROOK_BEHIND_PASSER_BONUS;
+                    } else {
+                        result -= ROOK_BEHIND_PASSER_BONUS;
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn development_value_for(&self, ally_color: Color) -> f64 {
+        let back_row = match ally_color {
+            WHITE => 0,
+            BLACK => 7,
+        };
+        let mut score = 0;
+        for piece in self.squares.iter().filter_map(|square| square.get_piece()) {
+            if piece.get_color() != ally_color {
+                continue;
+            }
+            let pos = piece.get_pos();
+            let home = pos.get_row() == back_row;
+            match piece {
+                Piece::Knight(..) if !(home && (pos.get_col() == 1 || pos.get_col() == 6)) => {
+                    score += DEVELOPED_MINOR_CP
+                }
+                Piece::Bishop(..) if !(home && (pos.get_col() == 2 || pos.get_col() == 5)) => {
+                    score += DEVELOPED_MINOR_CP
+                }
+                Piece::King(..) if home && pos.get_col() == 4 => score -= UNCASTLED_KING_CP,
+                _ => {}
+            }
+        }
+        self.taper(score, 0) as f64 / 100.0
+    }
+
     #[inline]
     fn get_current_player_color(&self) -> Color {
         self.turn
@@ -359,8 +648,12 @@ impl Evaluate for Board {
 
     #[inline]
     fn get_legal_moves(&self) -> Vec<Move> {
-        let mut result = vec![];
         let color = self.get_current_player_color();
+        if self.is_in_check(color) {
+            return self.evasion_moves();
+        }
+
+        let mut result = vec![];
         for square in &self.squares {
             if let Some(piece) = square.get_piece() {
                 if piece.get_color() == color {
@@ -408,6 +701,154 @@ impl Evaluate for Board {
         }
         return repr.to_string()
     }
+
+    #[inline]
+    fn zobrist_hash(&self) -> u64 {
+        self.zobrist()
+    }
+
+    fn get_capture_moves(&self) -> Vec<Move> {
+        self.get_legal_moves()
+            .into_iter()
+            .filter(|m| self.is_capture(*m))
+            .collect()
+    }
+
+    fn get_noisy_moves(&self) -> Vec<Move> {
+        self.get_legal_moves()
+            .into_iter()
+            .filter(|m| self.is_capture(*m) || self.is_promotion(*m))
+            .collect()
+    }
+
+    fn captured_value_cp(&self, m: Move) -> i32 {
+        self.captured_value_with(m, &self.piece_values)
+    }
+
+    fn order_moves(&self, mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort_by_cached_key(|m| {
+            let attacker = match m {
+                Move::Piece(from, _) | Move::Promotion(from, _, _) => self.get_piece(*from),
+                _ => None,
+            };
+            let attacker = match attacker {
+                Some(piece) => piece,
+                None => return (2, 0, 0),
+            };
+            if self.is_capture(*m) {
+                (0, -self.captured_value_cp(*m), self.value_of(attacker))
+            } else if self.is_promotion(*m) {
+                (1, 0, 0)
+            } else {
+                (2, 0, 0)
+            }
+        });
+        moves
+    }
+
+    fn see_with(&self, m: Move, values: &PieceValues) -> i32 {
+        let to = match m {
+            Move::Piece(_, to) | Move::Promotion(_, to, _) if self.is_capture(m) => to,
+            _ => return 0,
+        };
+        let after = self.apply_move(m);
+        match after.get_piece(to) {
+            Some(piece) => {
+                self.captured_value_with(m, values)
+                    - after.exchange_on(to, !piece.get_color(), values.value_cp(piece), values)
+            }
+            None => 0,
+        }
+    }
+}
+
+/// The value `Board::safety_map` gives an empty square.
+pub const EMPTY_SAFETY: i8 = i8::MIN;
+
+/// A knight or bishop with this many legal moves or fewer is trapped.
+const TRAPPED_MOBILITY: usize = 2;
+
+/// Material imbalance adjustments, in pawns. Two bishops cover both square
+/// colors, while a second knight or rook, or a rook next to a queen, mostly
+/// duplicates what the side already has.
+const BISHOP_PAIR_BONUS: f64 = 0.5;
+const KNIGHT_PAIR_PENALTY: f64 = 0.1;
+const ROOK_PAIR_PENALTY: f64 = 0.2;
+const QUEEN_AND_ROOK_PENALTY: f64 = 0.2;
+
+/// Bonus in pawns for a rook behind a passed pawn on an open stretch of file.
+const ROOK_BEHIND_PASSER_BONUS: f64 = 0.5;
+
+/// Opening development scores in centipawns, before tapering: a bonus for each
+/// knight or bishop off its starting square, and a penalty for a king that
+/// is still in the middle of the back rank.
+const DEVELOPED_MINOR_CP: i32 = 30;
+const UNCASTLED_KING_CP: i32 = 20;
+
+/// Get the material imbalance adjustment for one side from its piece counts.
+fn imbalance(knights: usize, bishops: usize, rooks: usize, queens: usize) -> f64 {
+    let mut result = 0.0;
+    if bishops >= 2 {
+        result += BISHOP_PAIR_BONUS;
+    }
+    if knights >= 2 {
+        result -= KNIGHT_PAIR_PENALTY;
+    }
+    if rooks >= 2 {
+        result -= ROOK_PAIR_PENALTY;
+    }
+    if queens >= 1 && rooks >= 1 {
+        result -= QUEEN_AND_ROOK_PENALTY;
+    }
+    result
+}
+
+// the side a castling move castles on
+fn castle_side(m: Move) -> CastleSide {
+    if m == Move::KingSideCastle {
+        CastleSide::KingSide
+    } else {
+        CastleSide::QueenSide
+    }
+}
+
+/// Get a bitboard with only the bit for `pos` set.
+#[inline]
+fn square_bit(pos: Position) -> u64 {
+    1 << (pos.get_row() * 8 + pos.get_col())
+}
+
+/// Get the bitboard of the squares one step away from `pos` in each of
+/// the given `(row, col)` directions, leaving out any that fall off the board.
+fn step_attacks(pos: Position, steps: &[(i32, i32)]) -> u64 {
+    let mut result = 0;
+    if pos.is_off_board() {
+        return result;
+    }
+    for (row, col) in steps {
+        let next = Position::new(pos.get_row() + row, pos.get_col() + col);
+        if next.is_on_board() {
+            result |= square_bit(next);
+        }
+    }
+    result
+}
+
+/// Get the bitboard of the squares strictly between `from` and `to`, or
+/// nothing if they don't share a rank, file or diagonal.
+fn squares_between(from: Position, to: Position) -> u64 {
+    let (rows, cols) = (to.get_row() - from.get_row(), to.get_col() - from.get_col());
+    if !(rows == 0 || cols == 0 || rows.abs() == cols.abs()) {
+        return 0;
+    }
+    let (row, col) = (rows.signum(), cols.signum());
+    let mut result = 0;
+    let mut next = Position::new(from.get_row() + row, from.get_col() + col);
+    while next != to {
+        result |= square_bit(next);
+        next = Position::new(next.get_row() + row, next.get_col() + col);
+    }
+    result
 }
 
 impl core::fmt::Display for Board {
@@ -509,11 +950,257 @@ impl Board {
 
             turn: WHITE,
             halfmoves: 0,
+            fullmoves: 1,
+
+            piece_values: PieceValues::default(),
         }
     }
 
+    /// Parse a board from Forsyth–Edwards Notation.
+    ///
+    /// Both the full six-field form and the common four-field form without
+    /// the move counters are accepted. Missing counters default to a
+    /// halfmove clock of 0 and a fullmove number of 1. Malformed input,
+    /// like a rank without exactly eight squares or an en passant square on
+    /// the wrong rank, is an error saying what was wrong.
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        parse_fen(fen)
+    }
+
+    /// Get the number of halfmoves since the last capture or pawn move.
+    #[inline]
+    pub fn halfmove_clock(&self) -> u8 {
+        self.halfmoves
+    }
+
+    /// Get the number of full moves since the last capture or pawn move,
+    /// rounded down.
+    #[inline]
+    pub fn moves_since_capture_or_pawn(&self) -> u32 {
+        self.halfmoves as u32 / 2
+    }
+
+    /// Can the current player claim a draw by the fifty move rule?
+    ///
+    /// A draw can be claimed once fifty moves by each side have gone by
+    /// without a capture or a pawn move. `play_move` doesn't wait for a
+    /// claim, and ends the game as soon as this is true.
+    #[inline]
+    pub fn can_claim_fifty_move(&self) -> bool {
+        self.halfmoves >= 100
+    }
+
+    /// Get the number of the current full move. This starts at 1 and goes
+    /// up every time Black moves.
+    #[inline]
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmoves
+    }
+
+    /// Write the board in six-field Forsyth–Edwards Notation, like
+    /// `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1`.
+    ///
+    /// The halfmove clock and fullmove number are the board's own, so
+    /// `Board::from_fen` reads back the same board.
     pub fn fen(&self) -> String {
-        format_fen(self, self.halfmoves, self.halfmoves / 2).unwrap()
+        format_fen(self)
+    }
+
+    /// Get only the piece placement field of the board's FEN, like
+    /// `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR`.
+    pub fn fen_placement(&self) -> String {
+        format_fen_placement(self)
+    }
+
+    /// Write a move in standard algebraic notation, like `Nbd2`, `exd5`,
+    /// `e8=Q+` or `O-O#`.
+    ///
+    /// The move is assumed to be legal for the current player. A pawn
+    /// moving to the last rank without a promotion is written as becoming
+    /// a queen, like the board plays it. Resigning is written `resign`.
+    pub fn move_to_san(&self, m: Move) -> String {
+        self.san_among(m, &self.get_legal_moves())
+    }
+
+    /// Write every legal move for the current player in standard
+    /// algebraic notation, generating the legal moves only once.
+    pub fn san_map(&self) -> BTreeMap<Move, String> {
+        let legal = self.get_legal_moves();
+        legal.iter().map(|m| (*m, self.san_among(*m, &legal))).collect()
+    }
+
+    /// Find the legal move written as `san`, the same way as
+    /// `Move::from_san`. Everything `move_to_san` writes is accepted.
+    pub fn move_from_san(&self, san: &str) -> Result<Move, String> {
+        Move::from_san(san, self)
+    }
+
+    /// Write a move in the long algebraic notation UCI uses, like `e2e4`,
+    /// `e1g1` for castling kingside as white, or `a7a8q`.
+    ///
+    /// A pawn moving to the last rank without a promotion is written as
+    /// becoming a queen, like the board plays it. Resigning has no UCI
+    /// form and is written `resign`.
+    pub fn move_to_uci(&self, m: Move) -> String {
+        let (from, to, promotion) = match m {
+            Move::KingSideCastle => {
+                let (from, to, _, _) = self.castling_squares(CastleSide::KingSide, self.turn);
+                (from, to, None)
+            }
+            Move::QueenSideCastle => {
+                let (from, to, _, _) = self.castling_squares(CastleSide::QueenSide, self.turn);
+                (from, to, None)
+            }
+            Move::Piece(from, to) => {
+                let promotes = matches!(self.get_piece(from), Some(Piece::Pawn(..)))
+                    && (to.get_row() == 0 || to.get_row() == 7);
+                (from, to, if promotes { Some(String::from("q")) } else { None })
+            }
+            Move::Promotion(from, to, piece) => (from, to, Some(piece.get_char().to_lowercase())),
+            Move::Resign => return String::from("resign"),
+        };
+        format!("{}{}{}", from, to, promotion.unwrap_or_default())
+    }
+
+    /// Read a move for the current player written in UCI long algebraic
+    /// notation. A king moving two squares from its starting square is
+    /// read as castling.
+    pub fn move_from_uci(&self, uci: &str) -> Result<Move, String> {
+        if uci.len() != 4 && uci.len() != 5 {
+            return Err(format!("invalid uci move `{}`", uci));
+        }
+        let from = Position::pgn(uci.get(0..2).ok_or("invalid uci move")?)?;
+        let to = Position::pgn(uci.get(2..4).ok_or("invalid uci move")?)?;
+        let offboard = Position::new(-1, -1);
+        let promotion = match uci.get(4..) {
+            Some("") => None,
+            Some("q") => Some(Piece::Queen(self.turn, offboard)),
+            Some("r") => Some(Piece::Rook(self.turn, offboard)),
+            Some("b") => Some(Piece::Bishop(self.turn, offboard)),
+            Some("n") => Some(Piece::Knight(self.turn, offboard)),
+            _ => return Err(format!("invalid promotion in uci move `{}`", uci)),
+        };
+
+        for (side, m) in [
+            (CastleSide::KingSide, Move::KingSideCastle),
+            (CastleSide::QueenSide, Move::QueenSideCastle),
+        ] {
+            let (king_from, king_to, _, _) = self.castling_squares(side, self.turn);
+            if (from, to) == (king_from, king_to)
+                && self.get_piece(from) == Some(Piece::King(self.turn, from))
+            {
+                return Ok(m);
+            }
+        }
+        Ok(match promotion {
+            Some(piece) => Move::Promotion(from, to, piece),
+            None => Move::Piece(from, to),
+        })
+    }
+
+    /// Write a move both in UCI and in standard algebraic notation.
+    pub fn annotate_move(&self, m: Move) -> MoveNotation {
+        MoveNotation {
+            uci: self.move_to_uci(m),
+            san: self.move_to_san(m),
+        }
+    }
+
+    // the SAN of `m`, disambiguated against the other moves in `legal`
+    fn san_among(&self, m: Move, legal: &[Move]) -> String {
+        let mut san = match m {
+            Move::KingSideCastle => String::from("O-O"),
+            Move::QueenSideCastle => String::from("O-O-O"),
+            Move::Resign => return String::from("resign"),
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => {
+                let piece = match self.get_piece(from) {
+                    Some(piece) => piece,
+                    None => return m.to_string(),
+                };
+                let from_square = from.to_string();
+                let capture = if self.is_capture(m) { "x" } else { "" };
+
+                if piece.is_pawn() {
+                    let mut san = if capture.is_empty() {
+                        to.to_string()
+                    } else {
+                        format!("{}x{}", &from_square[..1], to)
+                    };
+                    if to.get_row() == 0 || to.get_row() == 7 {
+                        let promotion = match m {
+                            Move::Promotion(_, _, promotion) => promotion.get_char(),
+                            _ => String::from("q"),
+                        };
+                        san += &format!("={}", promotion.to_uppercase());
+                    }
+                    san
+                } else {
+                    // the other pieces of the same kind that can also move to `to`
+                    let rivals: Vec<Position> = legal
+                        .iter()
+                        .filter_map(|other| match other {
+                            Move::Piece(other_from, other_to)
+                                if *other_to == to && *other_from != from =>
+                            {
+                                Some(*other_from)
+                            }
+                            _ => None,
+                        })
+                        .filter(|other_from| match self.get_piece(*other_from) {
+                            Some(other) => other.get_name() == piece.get_name(),
+                            None => false,
+                        })
+                        .collect();
+                    let disambiguation = if rivals.is_empty() {
+                        ""
+                    } else if rivals.iter().all(|r| r.get_col() != from.get_col()) {
+                        &from_square[..1]
+                    } else if rivals.iter().all(|r| r.get_row() != from.get_row()) {
+                        &from_square[1..]
+                    } else {
+                        &from_square[..]
+                    };
+                    format!(
+                        "{}{}{}{}",
+                        piece.get_char().to_uppercase(),
+                        disambiguation,
+                        capture,
+                        to
+                    )
+                }
+            }
+        };
+
+        let next = self.apply_eval_move(m);
+        if next.is_checkmate() {
+            san.push('#');
+        } else if next.is_in_check(next.turn) {
+            san.push('+');
+        }
+        san
+    }
+
+    /// Get a plain text diagram of the board for logs.
+    ///
+    /// Unlike `Display`, this always shows the board from White's side
+    /// without colors, with rank numbers down the left and file letters
+    /// along the bottom. White pieces are uppercase letters, black pieces
+    /// are lowercase, and empty squares are dots.
+    pub fn to_ascii_diagram(&self) -> String {
+        let mut result = String::new();
+        for row in (0..8).rev() {
+            result += &(row + 1).to_string();
+            for col in 0..8 {
+                result.push(' ');
+                match self.get_piece(Position::new(row, col)) {
+                    Some(piece) => result += &piece.get_char(),
+                    None => result.push('.'),
+                }
+            }
+            result.push('\n');
+        }
+        result += "  a b c d e f g h\n";
+        result
     }
 
     pub fn rating_bar(&self, len: usize) -> String {
@@ -594,6 +1281,27 @@ impl Board {
         result
     }
 
+    /// Give every piece to the other player and pass the turn, leaving every
+    /// piece on the square it stands on.
+    ///
+    /// Pawns keep moving the way their new color moves, so this mostly makes
+    /// sense for positions without pawns, or for telling apart bugs that come
+    /// from a piece's color from ones that come from where it stands. The
+    /// castling rights go with the pieces, and swapping twice gives back the
+    /// same board.
+    pub fn swap_colors(&self) -> Self {
+        let mut result = *self;
+        for square in &mut result.squares {
+            if let Some(piece) = square.get_piece() {
+                *square = Square::from(piece.flip_color())
+            }
+        }
+        result.white_castling_rights = self.black_castling_rights;
+        result.black_castling_rights = self.white_castling_rights;
+        result.turn = !self.turn;
+        result
+    }
+
     /// Make the game a certain player's turn
     #[inline]
     pub fn set_turn(&self, color: Color) -> Self {
@@ -602,33 +1310,200 @@ impl Board {
         result
     }
 
-    /// Get the value of the material advantage of a certain player
+    /// Use different piece values for material, static exchange evaluation
+    /// and capture ordering. The positions played from this one keep them.
+    #[inline]
+    pub fn set_piece_values(&self, values: PieceValues) -> Self {
+        let mut result = *self;
+        result.piece_values = values;
+        result
+    }
+
+    /// Get the value of a piece in centipawns, as material, static exchange
+    /// evaluation and capture ordering count it on this board.
+    ///
+    /// Every one of them reads the board's piece values, set with
+    /// `set_piece_values`, so they always agree. The ones that take a
+    /// `PieceValues` use `PieceValues::value_cp` with the values they're
+    /// given instead.
+    #[inline]
+    pub fn value_of(&self, piece: Piece) -> i32 {
+        self.piece_values.value_cp(piece)
+    }
+
+    /// Get the value of the material advantage of a certain player, in
+    /// whole pawns
     #[inline]
     pub fn get_material_advantage(&self, color: Color) -> i32 {
-        self.squares
+        let centipawns: i32 = self
+            .squares
             .iter()
             .map(|square| match square.get_piece() {
                 Some(piece) => {
                     if piece.get_color() == color {
-                        piece.get_material_value()
+                        self.value_of(piece)
                     } else {
-                        -piece.get_material_value()
+                        -self.value_of(piece)
                     }
                 }
                 None => 0,
             })
-            .sum()
+            .sum();
+        (centipawns as f64 / 100.0).round() as i32
     }
 
-    #[inline]
-    fn get_square(&mut self, pos: Position) -> &mut Square {
-        &mut self.squares[((7 - pos.get_row()) * 8 + pos.get_col()) as usize]
+    /// Get a key describing only the material on the board.
+    ///
+    /// The key packs how many of each piece type each color has,
+    /// so two positions share a key exactly when they have the same
+    /// material, no matter where the pieces stand or whose turn it is.
+    /// Pawn counts get six bits each (enough for horde), every other
+    /// piece count gets five.
+    pub fn material_key(&self) -> u64 {
+        let mut key = 0;
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                let shift = match piece {
+                    Piece::Pawn(..) => 0,
+                    Piece::Knight(..) => 6,
+                    Piece::Bishop(..) => 11,
+                    Piece::Rook(..) => 16,
+                    Piece::Queen(..) => 21,
+                    Piece::King(..) => 26,
+                } + if piece.get_color() == WHITE { 0 } else { 31 };
+                key += 1 << shift;
+            }
+        }
+        key
     }
 
-    #[inline]
-    fn add_piece(&mut self, piece: Piece) {
-        let pos = piece.get_pos();
-        *self.get_square(pos) = Square::from(piece);
+    /// Get how far the position is from the endgame.
+    ///
+    /// Knights and bishops count one, rooks two and queens four, so the
+    /// starting position has a phase of `MAX_PHASE` and a position with
+    /// only kings and pawns has a phase of zero. Extra promoted pieces
+    /// don't push the phase past `MAX_PHASE`.
+    pub fn phase(&self) -> i32 {
+        let phase: i32 = self
+            .squares
+            .iter()
+            .filter_map(|square| square.get_piece())
+            .map(|piece| match piece {
+                Piece::Knight(..) | Piece::Bishop(..) => 1,
+                Piece::Rook(..) => 2,
+                Piece::Queen(..) => 4,
+                Piece::Pawn(..) | Piece::King(..) => 0,
+            })
+            .sum();
+        phase.min(MAX_PHASE)
+    }
+
+    /// Blend a midgame and an endgame score by the phase of the position.
+    ///
+    /// At `MAX_PHASE` this is `mg_score`, at phase zero it is `eg_score`,
+    /// and in between the two are interpolated linearly. Evaluation terms
+    /// that differ between the midgame and the endgame should all go
+    /// through this, so they taper the same way.
+    pub fn taper(&self, mg_score: i32, eg_score: i32) -> i32 {
+        let phase = self.phase();
+        (mg_score * phase + eg_score * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+
+    /// Get the static evaluation of the board for the player to move, so a
+    /// positive score is good for whoever moves next. This is the score a
+    /// negamax search wants at its leaves.
+    pub fn score_relative(&self, engine: impl Into<EvalEngine>) -> f64 {
+        self.static_eval(self.turn, engine)
+    }
+
+    /// Get the six heuristic values for a player before any weighting, in
+    /// the same order as the weights of an engine: piece tables, mobility,
+    /// naive material, control, closest enemy to the king, and trades.
+    ///
+    /// The static evaluation with an engine that only has these six weights
+    /// is the dot product of these with its weights, which makes them the
+    /// gradient of the score with respect to the weights.
+    pub fn eval_components(&self, color: Color) -> [f64; 6] {
+        [
+            self.value_for(color),
+            self.mobility_value_for(color),
+            self.naive_value_for(color),
+            self.control_value_for(color),
+            self.closest_value_for(color),
+            self.trade_value_for(color),
+        ]
+    }
+
+    /// Get White's piece table score minus Black's.
+    ///
+    /// A checkmate costs both sides the same, so it doesn't show up here.
+    pub fn value_diff(&self) -> f64 {
+        self.value_for(WHITE) - self.value_for(BLACK)
+    }
+
+    /// Get White's mobility score minus Black's.
+    ///
+    /// Each side's mobility score is the negation of the other's, so this
+    /// only counts the moves on the board once.
+    pub fn mobility_diff(&self) -> f64 {
+        self.mobility_value_for(WHITE) * 2.0
+    }
+
+    /// Get White's naive material score minus Black's.
+    ///
+    /// Each side's material score is the negation of the other's, so this
+    /// only counts the material on the board once.
+    pub fn naive_diff(&self) -> f64 {
+        self.naive_value_for(WHITE) * 2.0
+    }
+
+    /// Get White's control score minus Black's.
+    pub fn control_diff(&self) -> f64 {
+        self.control_value_for(WHITE) - self.control_value_for(BLACK)
+    }
+
+    /// Get White's closest enemy score minus Black's.
+    pub fn closest_diff(&self) -> f64 {
+        self.closest_value_for(WHITE) - self.closest_value_for(BLACK)
+    }
+
+    /// Get White's trade score minus Black's.
+    pub fn trade_diff(&self) -> f64 {
+        self.trade_value_for(WHITE) - self.trade_value_for(BLACK)
+    }
+
+    /// Get White's trapped pieces score minus Black's.
+    ///
+    /// Each side's score is the negation of the other's, so this only looks
+    /// for trapped pieces once.
+    pub fn trapped_pieces_diff(&self) -> f64 {
+        self.trapped_pieces_value_for(WHITE) * 2.0
+    }
+
+    /// Get White's material imbalance score minus Black's.
+    pub fn material_imbalance_diff(&self) -> f64 {
+        self.material_imbalance_value_for(WHITE) - self.material_imbalance_value_for(BLACK)
+    }
+
+    /// Get White's rook behind passed pawn score minus Black's.
+    pub fn rook_behind_passer_diff(&self) -> f64 {
+        self.rook_behind_passer_value_for(WHITE) - self.rook_behind_passer_value_for(BLACK)
+    }
+
+    /// Get White's development score minus Black's.
+    pub fn development_diff(&self) -> f64 {
+        self.development_value_for(WHITE) - self.development_value_for(BLACK)
+    }
+
+    #[inline]
+    fn get_square(&mut self, pos: Position) -> &mut Square {
+        &mut self.squares[((7 - pos.get_row()) * 8 + pos.get_col()) as usize]
+    }
+
+    #[inline]
+    fn add_piece(&mut self, piece: Piece) {
+        let pos = piece.get_pos();
+        *self.get_square(pos) = Square::from(piece);
     }
 
     /// Does a square have any piece?
@@ -679,17 +1554,19 @@ impl Board {
         self.get_piece(pos) == None
     }
 
+    /// Get the square the king of a given color stands on, or `None`
+    /// if that color has no king on the board.
+    pub fn king_square(&self, color: Color) -> Option<Position> {
+        self.squares.iter().find_map(|square| match square.get_piece() {
+            Some(Piece::King(c, pos)) if c == color => Some(pos),
+            _ => None,
+        })
+    }
+
     /// If there is a king on the board, return the position that it sits on.
+    #[inline]
     pub fn get_king_pos(&self, color: Color) -> Option<Position> {
-        let mut king_pos = None;
-        for square in &self.squares {
-            if let Some(Piece::King(c, pos)) = square.get_piece() {
-                if c == color {
-                    king_pos = Some(pos);
-                }
-            }
-        }
-        king_pos
+        self.king_square(color)
     }
 
     /// Is a square threatened by an enemy piece?
@@ -745,318 +1622,3131 @@ impl Board {
         control
     }
 
-    /// Get whether or not the king of a given color is in check.
-    #[inline]
-    pub fn is_in_check(&self, color: Color) -> bool {
-        if let Some(king_pos) = self.get_king_pos(color) {
-            self.is_threatened(king_pos, color)
-        } else {
-            false
+    /// Get every piece, of either color, that attacks a given square.
+    ///
+    /// Pieces defending an ally on the square are included, so this
+    /// describes both sides of an exchange on the square in one pass.
+    pub fn pieces_attacking(&self, target: Position) -> Vec<(Position, Color, Piece)> {
+        let mut result = vec![];
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                if piece.attacks(target, self) {
+                    result.push((piece.get_pos(), piece.get_color(), piece));
+                }
+            }
         }
+        result
     }
 
-    fn move_piece(&self, from: Position, to: Position, promotion: Option<Piece>) -> Self {
-        let mut result = *self;
-        result.en_passant = None;
+    /// Get the squares of every piece of `color` that attacks `pos`.
+    ///
+    /// A pinned piece still attacks the squares it could move to if it weren't
+    /// pinned, and a pawn attacks its diagonals whether or not there is
+    /// anything on them to capture.
+    pub fn get_attackers(&self, pos: Position, color: Color) -> Vec<Position> {
+        self.pieces_attacking(pos)
+            .into_iter()
+            .filter(|(_, attacker_color, _)| *attacker_color == color)
+            .map(|(attacker, _, _)| attacker)
+            .collect()
+    }
 
-        if from.is_off_board() || to.is_off_board() {
-            return result;
+    /// Get how well defended every piece on the board is: the number of its
+    /// own pieces defending it minus the number of enemy pieces attacking it.
+    ///
+    /// The map is indexed `row * 8 + col`, like bitboards, and empty squares
+    /// hold `EMPTY_SAFETY`. A negative value is a piece that can be won by
+    /// simply trading on its square, whatever the pieces involved are worth.
+    pub fn safety_map(&self) -> [i8; 64] {
+        let mut result = [EMPTY_SAFETY; 64];
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                let pos = piece.get_pos();
+                let color = piece.get_color();
+                result[(pos.get_row() * 8 + pos.get_col()) as usize] = self
+                    .pieces_attacking(pos)
+                    .into_iter()
+                    .map(|(_, attacker_color, _)| if attacker_color == color { 1 } else { -1 })
+                    .sum();
+            }
         }
+        result
+    }
 
-        let from_square = result.get_square(from);
-        if let Some(mut piece) = from_square.get_piece() {
-            *from_square = EMPTY_SQUARE;
-
-            if piece.is_pawn() && (to.get_row() == 0 || to.get_row() == 7) {
-                piece = match promotion {
-                    // promotion only required to specify piece type
-                    Some(promotion) => {
-                        if promotion.is_king() || promotion.is_pawn() {
-                            // invalid promotion, use default
-                            Piece::Queen(piece.get_color(), piece.get_pos())
-                        } else {
-                            promotion
-                                .with_color(piece.get_color())
-                                .move_to(piece.get_pos())
-                        }
-                    }
-                    // queen by default
-                    None => Piece::Queen(piece.get_color(), piece.get_pos()),
-                }
+    /// Does moving the piece on `from` to `to` need the player to choose
+    /// what to promote to?
+    ///
+    /// This is true when the current player's pawn on `from` can legally
+    /// move to `to` and `to` is on the last rank, so an interface can ask
+    /// which piece to promote to before building the `Move::Promotion`.
+    pub fn needs_promotion_choice(&self, from: Position, to: Position) -> bool {
+        let last_row = match self.turn {
+            WHITE => 7,
+            BLACK => 0,
+        };
+        match self.get_piece(from) {
+            Some(piece) if piece.is_pawn() && to.get_row() == last_row => {
+                self.is_legal_move(Move::Piece(from, to), self.turn)
             }
+            _ => false,
+        }
+    }
 
-            if piece.is_starting_pawn() && (from.get_row() - to.get_row()).abs() == 2 {
-                result.en_passant = Some(to.pawn_back(piece.get_color()))
+    /// Is a move a capture? En passant captures count, castling never does.
+    pub fn is_capture(&self, m: Move) -> bool {
+        match m {
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => match self.get_piece(from) {
+                Some(Piece::Pawn(..)) if self.en_passant == Some(to) => from.get_col() != to.get_col(),
+                Some(piece) => self.has_enemy_piece(to, piece.get_color()),
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Is a move a pawn reaching the last rank, whether or not it says
+    /// what to promote to?
+    pub fn is_promotion(&self, m: Move) -> bool {
+        match m {
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => {
+                (to.get_row() == 0 || to.get_row() == 7)
+                    && self.get_piece(from).is_some_and(|piece| piece.is_pawn())
             }
+            _ => false,
+        }
+    }
 
-            result.add_piece(piece.move_to(to));
+    /// Get the static exchange evaluation of a capture in pawns, on the
+    /// same scale as `naive_value_for`. This is `see` divided by 100.
+    pub fn see_pawns(&self, m: Move) -> f64 {
+        self.see(m) as f64 / 100.0
+    }
 
-            let castling_rights = match piece.get_color() {
-                WHITE => &mut result.white_castling_rights,
-                BLACK => &mut result.black_castling_rights,
-            };
+    /// Get the capture that wins the most material for the current player
+    /// by static exchange evaluation, along with its value in centipawns.
+    ///
+    /// The best capture may still lose material if every capture does.
+    /// This returns `None` if the current player has no captures at all.
+    pub fn best_capture(&self) -> Option<(Move, i32)> {
+        self.best_capture_with(&self.piece_values)
+    }
 
-            if piece.is_king() {
-                castling_rights.disable_all();
-            } else if piece.is_queenside_rook() {
-                castling_rights.disable_queenside();
-            } else if piece.is_kingside_rook() {
-                castling_rights.disable_kingside();
+    /// Get the capture that wins the most material for the current player
+    /// like `best_capture`, counting the pieces traded with the given piece
+    /// values.
+    pub fn best_capture_with(&self, values: &PieceValues) -> Option<(Move, i32)> {
+        let mut best: Option<(Move, i32)> = None;
+        for m in self.get_capture_moves() {
+            let value = self.see_with(m, values);
+            if best.is_none_or(|(_, best_value)| value > best_value) {
+                best = Some((m, value));
             }
         }
+        best
+    }
 
-        result
+    /// Does a move put the other player in check?
+    ///
+    /// This counts discovered checks, where the move uncovers an attack by
+    /// another piece, as well as checks by the piece that moves.
+    pub fn gives_check(&self, m: Move) -> bool {
+        let next = self.apply_eval_move(m);
+        next.is_in_check(next.turn)
     }
 
-    /// Can a given player castle kingside?
-    pub fn can_kingside_castle(&self, color: Color) -> bool {
-        let right_of_king = Position::king_pos(color).next_right();
-        match color {
-            WHITE => {
-                self.has_no_piece(Position::new(0, 5))
-                    && self.has_no_piece(Position::new(0, 6))
-                    && self.get_piece(Position::new(0, 7))
-                        == Some(Piece::Rook(color, Position::new(0, 7)))
-                    && self.white_castling_rights.can_kingside_castle()
-                    && !self.is_in_check(color)
-                    && !self.is_threatened(right_of_king, color)
-                    && !self.is_threatened(right_of_king.next_right(), color)
-            }
-            BLACK => {
-                self.has_no_piece(Position::new(7, 5))
-                    && self.has_no_piece(Position::new(7, 6))
-                    && self.get_piece(Position::new(7, 7))
-                        == Some(Piece::Rook(color, Position::new(7, 7)))
-                    && self.black_castling_rights.can_kingside_castle()
-                    && !self.is_in_check(color)
-                    && !self.is_threatened(right_of_king, color)
-                    && !self.is_threatened(right_of_king.next_right(), color)
-            }
+    /// Get the legal moves for the current player that give check.
+    ///
+    /// Along with captures, these are the forcing moves a quiescence or
+    /// mate search looks at.
+    pub fn check_moves(&self) -> Vec<Move> {
+        self.get_legal_moves()
+            .into_iter()
+            .filter(|m| self.gives_check(*m))
+            .collect()
+    }
+
+    /// Pass the move to the other player without moving anything.
+    ///
+    /// Passing isn't a legal chess move, but searching the position after
+    /// it shows what the other player threatens. Any en passant square is
+    /// given up. This returns `None` when the current player is in check,
+    /// since passing would leave their king capturable.
+    pub fn null_move(&self) -> Option<Self> {
+        if self.is_in_check(self.turn) {
+            return None;
         }
+        let mut result = *self;
+        result.en_passant = None;
+        Some(result.change_turn())
     }
 
-    /// Can a given player castle queenside?
-    pub fn can_queenside_castle(&self, color: Color) -> bool {
-        match color {
-            WHITE => {
-                self.has_no_piece(Position::new(0, 1))
-                    && self.has_no_piece(Position::new(0, 2))
-                    && self.has_no_piece(Position::new(0, 3))
-                    && self.get_piece(Position::new(0, 0))
-                        == Some(Piece::Rook(color, Position::new(0, 0)))
-                    && self.white_castling_rights.can_queenside_castle()
-                    && !self.is_in_check(color)
-                    && !self.is_threatened(Position::queen_pos(color), color)
-            }
-            BLACK => {
-                self.has_no_piece(Position::new(7, 1))
-                    && self.has_no_piece(Position::new(7, 2))
-                    && self.has_no_piece(Position::new(7, 3))
-                    && self.get_piece(Position::new(7, 0))
-                        == Some(Piece::Rook(color, Position::new(7, 0)))
-                    && self.black_castling_rights.can_queenside_castle()
-                    && !self.is_in_check(color)
-                    && !self.is_threatened(Position::queen_pos(color), color)
-            }
+    /// Get the move the other player would make if the current player
+    /// passed, searched `depth` plies deep with `engine`.
+    ///
+    /// This is `None` if the current player is in check, or if the other
+    /// player would have no legal moves after the pass.
+    pub fn best_threat(&self, depth: i32, engine: impl Into<EvalEngine>) -> Option<Move> {
+        let (m, _, _) = self.null_move()?.get_best_next_move_cp(depth, engine);
+        match m {
+            Move::Resign => None,
+            m => Some(m),
         }
     }
 
-    pub fn get_castling_rights(&self, color: Color) -> CastlingRights {
-        match color {
-            WHITE => self.white_castling_rights,
-            BLACK => self.black_castling_rights,
+    /// Get the line of play the search expects from here with `depth` plies
+    /// of lookahead: the best move, then the best reply to it searched one
+    /// ply shallower, and so on down to the horizon.
+    ///
+    /// The line is `depth + 1` moves long, or shorter if the game ends first.
+    pub fn principal_variation(&self, depth: i32, engine: impl Into<EvalEngine>) -> Vec<Move> {
+        let engine = engine.into();
+        let mut line = vec![];
+        let mut board = *self;
+        for remaining in (0..=depth).rev() {
+            let (m, _, _) = board.get_best_next_move_cp(remaining, engine);
+            if m == Move::Resign {
+                break;
+            }
+            line.push(m);
+            board = board.apply_eval_move(m);
         }
+        line
     }
 
-    pub(crate) fn is_legal_move(&self, m: Move, player_color: Color) -> bool {
-        match m {
-            Move::KingSideCastle => self.can_kingside_castle(player_color),
-            Move::QueenSideCastle => self.can_queenside_castle(player_color),
-            Move::Piece(from, to) => match self.get_piece(from) {
-                Some(Piece::Pawn(c, pos)) => {
-                    let piece = Piece::Pawn(c, pos);
-                    ((if let Some(en_passant) = self.en_passant {
-                        (en_passant == from.pawn_up(player_color).next_left()
-                            || en_passant == from.pawn_up(player_color).next_right()
-                                && en_passant == to)
-                            && c == player_color
-                    } else {
-                        false
-                    }) || piece.is_legal_move(to, self) && piece.get_color() == player_color)
-                        && !self.apply_move(m).is_in_check(player_color)
-                }
-                Some(piece) => {
-                    piece.is_legal_move(to, self)
-                        && piece.get_color() == player_color
-                        && !self.apply_move(m).is_in_check(player_color)
-                }
-                _ => false,
-            },
-            Move::Promotion(from, to, promotion) => {
-                match self.get_piece(from) {
-                    Some(piece) => {
-                        // promotion specific checks
-                        piece.is_pawn()
-                            && (to.get_row() == 0 || to.get_row() == 7)
-                            && !(promotion.is_king() || promotion.is_pawn())
-                            // regular piece checks
-                            && piece.is_legal_move(to, self)
-                            && piece.get_color() == player_color
-                            && !self.apply_move(m).is_in_check(player_color)
-                    }
-                    _ => false,
-                }
+    /// Get the principal variation from here, along with the heuristic values
+    /// of the position at its end for the current player, as `eval_components`
+    /// gives them.
+    ///
+    /// The search rates a move by the position it leads to at the horizon,
+    /// so these values show what the engine expects to gain by playing it.
+    pub fn evaluate_with_trace(&self, depth: i32, engine: impl Into<EvalEngine>) -> (Vec<Move>, [f64; 6]) {
+        let line = self.principal_variation(depth, engine);
+        let leaf = line.iter().fold(*self, |board, m| board.apply_eval_move(*m));
+        (line, leaf.eval_components(self.turn))
+    }
+
+    /// Split the static evaluation for a player into the part that counts
+    /// material and the part that counts everything else, in points.
+    ///
+    /// The piece tables count each piece's material apart from where it
+    /// stands, so the first goes to the material part and the second to the
+    /// positional part. Naive material and material imbalance are material,
+    /// and every other term is positional. The two parts add up to
+    /// `static_eval`.
+    pub fn material_and_positional_split(&self, color: Color, engine: impl Into<EvalEngine>) -> (f64, f64) {
+        let engine = engine.into();
+        let mut material = 0.0;
+        let mut positional = 0.0;
+
+        if engine.material != 0.0 {
+            let mut tables = (0.0, 0.0);
+            for piece in self.squares.iter().filter_map(|square| square.get_piece()) {
+                let sign = if piece.get_color() == color { 1.0 } else { -1.0 };
+                tables.0 += sign * (piece.get_material_value() * 10) as f64;
+                tables.1 += sign * piece.get_position_weight();
             }
-            Move::Resign => true,
+            if self.is_checkmate() {
+                tables.1 -= 999.0;
+            }
+            material += tables.0 * engine.material;
+            positional += tables.1 * engine.material;
+        }
+        if engine.naive != 0.0 {
+            material += self.naive_value_for(color) * engine.naive
+        }
+        if engine.material_imbalance != 0.0 {
+            material += self.material_imbalance_value_for(color) * engine.material_imbalance
+        }
+
+        if engine.mobility != 0.0 {
+            positional += self.mobility_value_for(color) * engine.mobility
+        }
+        if engine.control != 0.0 {
+            positional += self.control_value_for(color) * engine.control
         }
+        if engine.closest != 0.0 {
+            positional += self.closest_value_for(color) * engine.closest
+        }
+        if engine.trade != 0.0 {
+            positional += self.trade_value_for(color) * engine.trade
+        }
+        if engine.trapped_pieces != 0.0 {
+            positional += self.trapped_pieces_value_for(color) * engine.trapped_pieces
+        }
+        if engine.rook_behind_passer != 0.0 {
+            positional += self.rook_behind_passer_value_for(color) * engine.rook_behind_passer
+        }
+        if engine.development != 0.0 {
+            positional += self.development_value_for(color) * engine.development
+        }
+        (material, positional)
     }
 
-    /// Does the respective player have sufficient material?
-    pub fn has_sufficient_material(&self, color: Color) -> bool {
-        let mut pieces = vec![];
+    /// Search for the best move like `Evaluate::search`, and also split the
+    /// evaluation at the end of the principal variation into material and
+    /// positional parts with `material_and_positional_split`, so it can
+    /// show a side that is up material but worse off otherwise.
+    ///
+    /// This searches the principal variation out move by move, which takes
+    /// a lot longer than the search alone.
+    pub fn analyze(&self, depth: i32, engine: impl Into<EvalEngine>) -> SearchResult {
+        let engine = engine.into();
+        let mut result = self.search(depth, engine);
+        let line = self.principal_variation(depth, engine);
+        let leaf = line.iter().fold(*self, |board, m| board.apply_eval_move(*m));
+        let (material, positional) = leaf.material_and_positional_split(self.turn, engine);
+        result.split = Some(EvalSplit {
+            material: (material * CENTIPAWNS_PER_POINT).round() as i32,
+            positional: (positional * CENTIPAWNS_PER_POINT).round() as i32,
+        });
+        result
+    }
+
+    /// Count the legal captures available to the current player.
+    ///
+    /// This is a rough measure of how tactical a position is.
+    pub fn capture_count(&self) -> usize {
+        let mut count = 0;
         for square in &self.squares {
             if let Some(piece) = square.get_piece() {
-                if piece.get_color() == color {
-                    pieces.push(piece);
+                if piece.get_color() == self.turn {
+                    count += piece
+                        .get_legal_moves(self)
+                        .into_iter()
+                        .filter(|m| self.is_capture(*m))
+                        .count();
                 }
             }
         }
+        count
+    }
 
-        pieces.sort();
-
-        if pieces.len() == 0 {
-            false
-        } else if pieces.len() == 1 && pieces[0].is_king() {
-            false
-        } else if pieces.len() == 2 && pieces[0].is_king() && pieces[1].is_knight() {
-            false
-        } else if pieces.len() == 2 && pieces[0].is_king() && pieces[1].is_bishop() {
-            false
-        } else if pieces.len() == 3
-            && pieces[0].is_king()
-            && pieces[1].is_knight()
-            && pieces[2].is_knight()
-        {
-            false
-        } else if pieces.len() == 3
-            && pieces[0].is_king()
-            && pieces[1].is_bishop()
-            && pieces[2].is_bishop()
-        {
-            false
-        } else {
-            true
+    /// Get the value in centipawns of the piece a move captures, counted
+    /// with the given piece values.
+    fn captured_value_with(&self, m: Move, values: &PieceValues) -> i32 {
+        match m {
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => match self.get_piece(to) {
+                Some(piece) => values.value_cp(piece),
+                None if self.is_capture(m) => values.value_cp(Piece::Pawn(self.turn, from)),
+                None => 0,
+            },
+            _ => 0,
         }
     }
 
-    /// Does the respective player have insufficient material?
-    #[inline]
-    pub fn has_insufficient_material(&self, color: Color) -> bool {
-        !self.has_sufficient_material(color)
+    /// Get how much material `side` can win by recapturing on `target`,
+    /// where the enemy piece worth `occupant` centipawns now stands.
+    ///
+    /// Each recapture is made with the least valuable attacker, and either
+    /// side may stop recapturing whenever continuing would lose material.
+    fn exchange_on(&self, target: Position, side: Color, occupant: i32, values: &PieceValues) -> i32 {
+        self.exchange_line(target, side, occupant, values).0
     }
 
-    /// Is the current player in stalemate?
-    pub fn is_stalemate(&self) -> bool {
-        (self.get_legal_moves().is_empty() && !self.is_in_check(self.get_current_player_color()))
-            || (self.has_insufficient_material(self.turn) && self.has_insufficient_material(!self.turn))
-            || (self.halfmoves > 100)
+    /// Like `exchange_on`, but also get the captures the exchange is made
+    /// of, in order, each given as the square it's made from and the piece
+    /// making it. A side that stops recapturing ends the list.
+    fn exchange_line(
+        &self,
+        target: Position,
+        side: Color,
+        occupant: i32,
+        values: &PieceValues,
+    ) -> (i32, Vec<(Position, Piece)>) {
+        match self.least_valuable_attacker(target, side, values) {
+            Some((pos, piece)) => {
+                let (rest, mut line) =
+                    self.capture_with(pos, piece, target).exchange_line(target, !side, values.value_cp(piece), values);
+                if occupant - rest > 0 {
+                    line.insert(0, (pos, piece));
+                    (occupant - rest, line)
+                } else {
+                    (0, vec![])
+                }
+            }
+            None => (0, vec![]),
+        }
     }
 
-    /// Is the current player in checkmate?
-    pub fn is_checkmate(&self) -> bool {
-        self.is_in_check(self.get_current_player_color()) && self.get_legal_moves().is_empty()
+    /// Get the least valuable piece of `side` attacking `target`.
+    fn least_valuable_attacker(&self, target: Position, side: Color, values: &PieceValues) -> Option<(Position, Piece)> {
+        self.pieces_attacking(target)
+            .into_iter()
+            .filter(|(_, color, _)| *color == side)
+            .min_by_key(|(_, _, piece)| values.value_cp(*piece))
+            .map(|(pos, _, piece)| (pos, piece))
     }
 
-    /// Change the current turn to the next player.
-    #[inline]
-    pub fn change_turn(mut self) -> Self {
-        self.turn = !self.turn;
-        self
+    /// Get the board after the piece on `pos` takes whatever is on
+    /// `target`, without checking the capture is legal or passing the turn.
+    fn capture_with(&self, pos: Position, piece: Piece, target: Position) -> Self {
+        let mut board = *self;
+        *board.get_square(pos) = EMPTY_SQUARE;
+        *board.get_square(target) = Square::from(piece.move_to(target));
+        board
     }
 
-    fn apply_move(&self, m: Move) -> Self {        
-        match m {
-            Move::KingSideCastle => {
-                if let Some(king_pos) = self.get_king_pos(self.turn) {
-                    let rook_pos = match self.turn {
-                        WHITE => Position::new(0, 7),
-                        BLACK => Position::new(7, 7),
-                    };
-                    let mut result = self.move_piece(king_pos, rook_pos.next_left(), None)
-                        .move_piece(rook_pos, king_pos.next_right(), None);
-                    result.halfmoves += 1;
-                    result
-                } else {
-                    *self
+    /// Get the captures static exchange evaluation assumes are made on
+    /// `target`, in order, each given as the square it's made from and the
+    /// piece making it.
+    ///
+    /// The piece on the target is always taken by its least valuable
+    /// attacker. After that each side recaptures with its least valuable
+    /// attacker for as long as doing so wins material. This is empty if
+    /// the target is empty or nothing attacks the piece on it.
+    pub fn exchange_sequence(&self, target: Position) -> Vec<(Position, Piece)> {
+        let values = self.piece_values;
+        let side = match self.get_piece(target) {
+            Some(piece) => !piece.get_color(),
+            None => return vec![],
+        };
+        match self.least_valuable_attacker(target, side, &values) {
+            Some((pos, piece)) => {
+                let (_, mut line) =
+                    self.capture_with(pos, piece, target).exchange_line(target, !side, values.value_cp(piece), &values);
+                line.insert(0, (pos, piece));
+                line
+            }
+            None => vec![],
+        }
+    }
+
+    /// Get the legal moves of the piece on a square, whichever color it is.
+    ///
+    /// An empty square has no moves. Castling is listed under the king.
+    pub fn moves_of_piece_at(&self, pos: Position) -> Vec<Move> {
+        match self.get_piece(pos) {
+            Some(piece) => piece.get_legal_moves(self),
+            None => vec![],
+        }
+    }
+
+    /// Get every legal move for the current player along with the board
+    /// it leads to, where it is the other player's turn.
+    pub fn expand(&self) -> Vec<(Move, Self)> {
+        self.get_legal_moves()
+            .into_iter()
+            .map(|m| (m, self.apply_eval_move(m)))
+            .collect()
+    }
+
+    /// Get the number of legal moves of the piece on a square, whichever
+    /// color it is. This is what the mobility heuristic counts per piece.
+    pub fn piece_mobility(&self, pos: Position) -> usize {
+        self.moves_of_piece_at(pos).len()
+    }
+
+    /// Get the legal moves for the current player, grouped by the square
+    /// of the piece that makes them. Castling is grouped under the king.
+    pub fn legal_moves_grouped(&self) -> BTreeMap<Position, Vec<Move>> {
+        let mut result: BTreeMap<Position, Vec<Move>> = BTreeMap::new();
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                if piece.get_color() == self.turn {
+                    let moves = piece.get_legal_moves(self);
+                    if !moves.is_empty() {
+                        result.insert(piece.get_pos(), moves);
+                    }
                 }
             }
-            Move::QueenSideCastle => {
-                if let Some(king_pos) = self.get_king_pos(self.turn) {
-                    let rook_pos = match self.turn {
-                        WHITE => Position::new(0, 0),
-                        BLACK => Position::new(7, 0),
-                    };
-                    let mut result = self.move_piece(king_pos, king_pos.next_left().next_left(), None)
-                        .move_piece(rook_pos, king_pos.next_left(), None);
-                    result.halfmoves += 1;
-                    result
-                } else {
-                    *self
+        }
+        result
+    }
+
+    /// Get the pseudo-legal moves of the piece on a square: every move it
+    /// could make if leaving its own king in check were allowed.
+    ///
+    /// Comparing this with `moves_of_piece_at` shows which moves the second
+    /// stage of move generation throws out.
+    pub fn pseudo_legal_moves_of_piece_at(&self, pos: Position) -> Vec<Move> {
+        match self.get_piece(pos) {
+            Some(piece) => piece.get_pseudo_legal_moves(self),
+            None => vec![],
+        }
+    }
+
+    /// Get how much of the center `color` controls.
+    ///
+    /// Each of d4, e4, d5 and e5 that `color` attacks or occupies counts
+    /// two, and each other square from c3 to f6 counts one, so full control
+    /// of the center is worth 20.
+    pub fn center_control(&self, color: Color) -> i32 {
+        let mut controlled = 0;
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                if piece.get_color() == color {
+                    controlled |= square_bit(piece.get_pos()) | self.attack_map(piece.get_pos());
                 }
             }
+        }
 
-            Move::Piece(from, to) => {
-                let mut result = self.move_piece(from, to, None);
-                result.halfmoves += 1;
-                if self.get_piece(from).unwrap().get_name() == "pawn" {
-                    //Pawn Move
-                    result.halfmoves = 0;
+        let mut result = 0;
+        for row in 2..6 {
+            for col in 2..6 {
+                if controlled & square_bit(Position::new(row, col)) != 0 {
+                    let core = (3..5).contains(&row) && (3..5).contains(&col);
+                    result += if core { 2 } else { 1 };
                 }
-                if self.has_piece(to) {
-                    result.halfmoves = 0;
+            }
+        }
+        result
+    }
+
+    /// Get the squares a knight on `pos` attacks, as a bitboard.
+    ///
+    /// Bitboards number the squares `row * 8 + col`, so A1 is the lowest
+    /// bit and H8 is the highest.
+    pub fn knight_attacks(pos: Position) -> u64 {
+        step_attacks(
+            pos,
+            &[(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)],
+        )
+    }
+
+    /// Get the squares a king on `pos` attacks, as a bitboard.
+    pub fn king_attacks(pos: Position) -> u64 {
+        step_attacks(
+            pos,
+            &[(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)],
+        )
+    }
+
+    /// Get the squares a pawn of a given color on `pos` attacks, as a
+    /// bitboard. This doesn't include en passant captures.
+    pub fn pawn_attacks(pos: Position, color: Color) -> u64 {
+        let up = match color {
+            WHITE => 1,
+            BLACK => -1,
+        };
+        step_attacks(pos, &[(up, -1), (up, 1)])
+    }
+
+    /// Get the squares a bishop on `pos` attacks on this board, as a
+    /// bitboard. Each diagonal stops at the first piece in the way, which
+    /// is included whatever its color.
+    pub fn bishop_attacks(&self, pos: Position) -> u64 {
+        self.ray_attacks(pos, &[(1, 1), (1, -1), (-1, 1), (-1, -1)])
+    }
+
+    /// Get the squares a rook on `pos` attacks on this board, as a
+    /// bitboard. Each line stops at the first piece in the way, which
+    /// is included whatever its color.
+    pub fn rook_attacks(&self, pos: Position) -> u64 {
+        self.ray_attacks(pos, &[(1, 0), (-1, 0), (0, 1), (0, -1)])
+    }
+
+    /// Get the squares a queen on `pos` attacks on this board, as a bitboard.
+    pub fn queen_attacks(&self, pos: Position) -> u64 {
+        self.bishop_attacks(pos) | self.rook_attacks(pos)
+    }
+
+    /// Get the squares attacked by the piece on `pos`, as a bitboard.
+    /// An empty square attacks nothing.
+    pub fn attack_map(&self, pos: Position) -> u64 {
+        match self.get_piece(pos) {
+            Some(Piece::King(..)) => Self::king_attacks(pos),
+            Some(Piece::Queen(..)) => self.queen_attacks(pos),
+            Some(Piece::Rook(..)) => self.rook_attacks(pos),
+            Some(Piece::Bishop(..)) => self.bishop_attacks(pos),
+            Some(Piece::Knight(..)) => Self::knight_attacks(pos),
+            Some(Piece::Pawn(color, _)) => Self::pawn_attacks(pos, color),
+            None => 0,
+        }
+    }
+
+    fn ray_attacks(&self, pos: Position, directions: &[(i32, i32)]) -> u64 {
+        let mut result = 0;
+        if pos.is_off_board() {
+            return result;
+        }
+        for (row, col) in directions {
+            let mut next = Position::new(pos.get_row() + row, pos.get_col() + col);
+            while next.is_on_board() {
+                result |= square_bit(next);
+                if self.has_piece(next) {
+                    break;
                 }
+                next = Position::new(next.get_row() + row, next.get_col() + col);
+            }
+        }
+        result
+    }
 
-                if let (Some(en_passant), Some(Piece::Pawn(player_color, _))) =
-                    (self.en_passant, self.get_piece(from))
-                {
-                    if (en_passant == from.pawn_up(player_color).next_left()
-                        || en_passant == from.pawn_up(player_color).next_right())
-                        && en_passant == to
-                    {
-                        result.squares[((7 - en_passant.pawn_back(player_color).get_row()) * 8
-                            + en_passant.get_col())
-                            as usize] = EMPTY_SQUARE;
+    /// Is the piece on `pos` a passed pawn?
+    ///
+    /// A pawn is passed when no enemy pawn stands ahead of it on its own
+    /// file or on either adjacent file, so no enemy pawn can block or
+    /// capture it on its way to promotion. Anything other than a pawn is
+    /// never passed.
+    pub fn is_passed_pawn(&self, pos: Position) -> bool {
+        let color = match self.get_piece(pos) {
+            Some(Piece::Pawn(color, _)) => color,
+            _ => return false,
+        };
+
+        for row in 0..8 {
+            let ahead = match color {
+                WHITE => row > pos.get_row(),
+                BLACK => row < pos.get_row(),
+            };
+            if !ahead {
+                continue;
+            }
+            for col in pos.get_col() - 1..=pos.get_col() + 1 {
+                if let Some(Piece::Pawn(enemy_color, _)) = self.get_piece(Position::new(row, col)) {
+                    if enemy_color != color {
+                        return false;
                     }
                 }
-
-                result
             }
-            Move::Promotion(from, to, promotion) => {
-                let mut result = self.move_piece(from, to, Some(promotion));
-                result.halfmoves += 1;
-                result
+        }
+        true
+    }
+
+    /// Get how many squares the pawn on `pos` has left to advance before it
+    /// promotes, and whether any piece stands on its file in the way.
+    ///
+    /// This is `None` if there is no pawn on `pos`. Comparing the distance
+    /// with how far the enemy king is from the promotion square gives the
+    /// rule of the square for pawn races.
+    pub fn promotion_distance(&self, pos: Position) -> Option<(u32, bool)> {
+        let (distance, ahead) = match self.get_piece(pos) {
+            Some(Piece::Pawn(WHITE, _)) => (7 - pos.get_row(), pos.get_row() + 1..8),
+            Some(Piece::Pawn(BLACK, _)) => (pos.get_row(), 0..pos.get_row()),
+            _ => return None,
+        };
+        let blocked = ahead
+            .into_iter()
+            .any(|row| self.has_piece(Position::new(row, pos.get_col())));
+        Some((distance as u32, blocked))
+    }
+
+    /// Get whether or not the king of a given color is in check, whichever
+    /// player is to move. A color without a king on the board is never in check.
+    #[inline]
+    pub fn is_in_check(&self, color: Color) -> bool {
+        if let Some(king_pos) = self.king_square(color) {
+            self.is_threatened(king_pos, color)
+        } else {
+            false
+        }
+    }
+
+    /// Get the legal moves for the current player while they are in check.
+    ///
+    /// Only moves that could get out of check are tried: king moves, captures
+    /// of the checking piece, and moves onto the line between the king and a
+    /// checking queen, rook or bishop. In double check only the king can move.
+    /// The moves come in the same order `get_legal_moves` gives them, and this
+    /// is empty if the current player isn't in check.
+    pub fn evasion_moves(&self) -> Vec<Move> {
+        let color = self.turn;
+        let king = match self.king_square(color) {
+            Some(pos) => pos,
+            None => return vec![],
+        };
+        let checkers: Vec<(Position, Piece)> = self
+            .pieces_attacking(king)
+            .into_iter()
+            .filter(|(_, checker_color, _)| *checker_color != color)
+            .map(|(pos, _, piece)| (pos, piece))
+            .collect();
+
+        let (targets, checker_is_pawn) = match checkers.as_slice() {
+            [] => return vec![],
+            [(pos, piece)] => match piece {
+                Piece::Queen(..) | Piece::Rook(..) | Piece::Bishop(..) => {
+                    (square_bit(*pos) | squares_between(king, *pos), false)
+                }
+                _ => (square_bit(*pos), piece.is_pawn()),
             },
-            Move::Resign => self.remove_all(self.turn).queen_all(!self.turn),
+            _ => (0, false),
+        };
+
+        let mut result = vec![];
+        for square in &self.squares {
+            let piece = match square.get_piece() {
+                Some(piece) if piece.get_color() == color => piece,
+                _ => continue,
+            };
+            for m in piece.get_pseudo_legal_moves(self) {
+                let evades = match m {
+                    _ if piece.is_king() => true,
+                    Move::Piece(_, to) | Move::Promotion(_, to, _) => {
+                        targets & square_bit(to) != 0
+                            // en passant takes a checking pawn without landing on it
+                            || (checker_is_pawn && piece.is_pawn() && self.en_passant == Some(to))
+                    }
+                    _ => false,
+                };
+                if evades && self.is_legal_move(m, color) {
+                    result.push(m);
+                }
+            }
         }
+        result
     }
 
-    /// Play a move and confirm it is legal.
-    pub fn play_move(&self, m: Move) -> GameResult {
-        let current_color = self.get_turn_color();
+    // move the piece on `from` to `to`, and get the piece as it stood
+    // before it moved
+    fn move_piece(&mut self, from: Position, to: Position, promotion: Option<Piece>) -> Option<Piece> {
+        self.en_passant = None;
 
-        if m == Move::Resign {
-            GameResult::Victory(!current_color)
-        } else if self.is_legal_move(m, current_color) {
-            let next_turn = self.apply_move(m).change_turn();
-            if next_turn.is_checkmate() {
-                GameResult::Victory(current_color)
-            } else if next_turn.is_stalemate() {
-                GameResult::Stalemate
-            } else {
-                GameResult::Continuing(next_turn)
+        if from.is_off_board() || to.is_off_board() {
+            return None;
+        }
+
+        let from_square = self.get_square(from);
+        let moved = from_square.get_piece();
+        if let Some(mut piece) = moved {
+            *from_square = EMPTY_SQUARE;
+
+            if piece.is_pawn() && (to.get_row() == 0 || to.get_row() == 7) {
+                piece = match promotion {
+                    // promotion only required to specify piece type
+                    Some(promotion) => {
+                        if promotion.is_king() || promotion.is_pawn() {
+                            // invalid promotion, use default
+                            Piece::Queen(piece.get_color(), piece.get_pos())
+                        } else {
+                            promotion
+                                .with_color(piece.get_color())
+                                .move_to(piece.get_pos())
+                        }
+                    }
+                    // queen by default
+                    None => Piece::Queen(piece.get_color(), piece.get_pos()),
+                }
+            }
+
+            if piece.is_starting_pawn() && (from.get_row() - to.get_row()).abs() == 2 {
+                self.en_passant = Some(to.pawn_back(piece.get_color()))
             }
+
+            self.add_piece(piece.move_to(to));
+
+            let castling_rights = match piece.get_color() {
+                WHITE => &mut self.white_castling_rights,
+                BLACK => &mut self.black_castling_rights,
+            };
+
+            if piece.is_king() {
+                castling_rights.disable_all();
+            } else if piece.is_queenside_rook() {
+                castling_rights.disable_queenside();
+            } else if piece.is_kingside_rook() {
+                castling_rights.disable_kingside();
+            }
+        }
+
+        moved
+    }
+
+    /// The squares a castle moves pieces between, as
+    /// `(king_from, king_to, rook_from, rook_to)`.
+    ///
+    /// Castling kingside as white moves the king from E1 to G1 and the rook
+    /// from H1 to F1; castling queenside moves the king from E1 to C1 and the
+    /// rook from A1 to D1. Black castles the same way on the eighth rank.
+    pub fn castling_squares(&self, side: CastleSide, color: Color) -> (Position, Position, Position, Position) {
+        let king_from = Position::king_pos(color);
+        let row = king_from.get_row();
+        match side {
+            CastleSide::KingSide => (
+                king_from,
+                Position::new(row, 6),
+                Position::new(row, 7),
+                Position::new(row, 5),
+            ),
+            CastleSide::QueenSide => (
+                king_from,
+                Position::new(row, 2),
+                Position::new(row, 0),
+                Position::new(row, 3),
+            ),
+        }
+    }
+
+    /// Can a given player castle kingside?
+    #[inline]
+    pub fn can_kingside_castle(&self, color: Color) -> bool {
+        self.castle_legality_for(color, CastleSide::KingSide).is_legal()
+    }
+
+    /// Can a given player castle queenside?
+    #[inline]
+    pub fn can_queenside_castle(&self, color: Color) -> bool {
+        self.castle_legality_for(color, CastleSide::QueenSide).is_legal()
+    }
+
+    /// Why can (or can't) the current player castle to a given side?
+    pub fn castle_legality(&self, side: CastleSide) -> CastleLegality {
+        self.castle_legality_for(self.turn, side)
+    }
+
+    /// Why can (or can't) a given player castle to a given side?
+    ///
+    /// Castling requires that
+    /// 1. The king has not moved since the game began
+    /// 2. The respective rook has not moved either
+    /// 3. Every square between the king and the rook is empty
+    /// 4. The king is not currently in check
+    /// 5. The square the king passes over is not threatened
+    /// 6. The square the king lands on is not threatened
+    ///
+    /// The first failing condition, in that order, is returned.
+    pub fn castle_legality_for(&self, color: Color, side: CastleSide) -> CastleLegality {
+        let king_pos = Position::king_pos(color);
+        let rights = self.get_castling_rights(color);
+        if self.get_piece(king_pos) != Some(Piece::King(color, king_pos))
+            || (!rights.can_kingside_castle() && !rights.can_queenside_castle())
+        {
+            return CastleLegality::KingMoved;
+        }
+
+        let (_, landing, rook_pos, passed) = self.castling_squares(side, color);
+        let (has_right, empty) = match side {
+            CastleSide::KingSide => (rights.can_kingside_castle(), vec![passed, landing]),
+            CastleSide::QueenSide => (
+                rights.can_queenside_castle(),
+                vec![passed, landing, landing.next_left()],
+            ),
+        };
+
+        if !has_right || self.get_piece(rook_pos) != Some(Piece::Rook(color, rook_pos)) {
+            CastleLegality::RookMoved
+        } else if empty.into_iter().any(|pos| self.has_piece(pos)) {
+            CastleLegality::PathBlocked
+        } else if self.is_in_check(color) {
+            CastleLegality::InCheck
+        } else if self.is_threatened(passed, color) {
+            CastleLegality::ThroughCheck
+        } else if self.is_threatened(landing, color) {
+            CastleLegality::IntoCheck
         } else {
-            GameResult::IllegalMove(m)
+            CastleLegality::Legal
+        }
+    }
+
+    pub fn get_castling_rights(&self, color: Color) -> CastlingRights {
+        match color {
+            WHITE => self.white_castling_rights,
+            BLACK => self.black_castling_rights,
+        }
+    }
+
+    pub(crate) fn is_legal_move(&self, m: Move, player_color: Color) -> bool {
+        match m {
+            Move::KingSideCastle => self.can_kingside_castle(player_color),
+            Move::QueenSideCastle => self.can_queenside_castle(player_color),
+            Move::Piece(from, to) => match self.get_piece(from) {
+                Some(Piece::Pawn(c, pos)) => {
+                    let piece = Piece::Pawn(c, pos);
+                    ((if let Some(en_passant) = self.en_passant {
+                        (en_passant == from.pawn_up(player_color).next_left()
+                            || en_passant == from.pawn_up(player_color).next_right())
+                            && en_passant == to
+                            && c == player_color
+                    } else {
+                        false
+                    }) || piece.is_legal_move(to, self) && piece.get_color() == player_color)
+                        && !self.apply_move(m).is_in_check(player_color)
+                }
+                Some(piece) => {
+                    piece.is_legal_move(to, self)
+                        && piece.get_color() == player_color
+                        && !self.apply_move(m).is_in_check(player_color)
+                }
+                _ => false,
+            },
+            Move::Promotion(from, to, promotion) => {
+                match self.get_piece(from) {
+                    Some(piece) => {
+                        // promotion specific checks
+                        piece.is_pawn()
+                            && (to.get_row() == 0 || to.get_row() == 7)
+                            && !(promotion.is_king() || promotion.is_pawn())
+                            // regular piece checks
+                            && piece.is_legal_move(to, self)
+                            && piece.get_color() == player_color
+                            && !self.apply_move(m).is_in_check(player_color)
+                    }
+                    _ => false,
+                }
+            }
+            Move::Resign => true,
+        }
+    }
+
+    /// Does the respective player have sufficient material?
+    pub fn has_sufficient_material(&self, color: Color) -> bool {
+        let mut pieces = vec![];
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                if piece.get_color() == color {
+                    pieces.push(piece);
+                }
+            }
+        }
+
+        pieces.sort();
+
+        if pieces.len() == 0 {
+            false
+        } else if pieces.len() == 1 && pieces[0].is_king() {
+            false
+        } else if pieces.len() == 2 && pieces[0].is_king() && pieces[1].is_knight() {
+            false
+        } else if pieces.len() == 2 && pieces[0].is_king() && pieces[1].is_bishop() {
+            false
+        } else if pieces.len() == 3
+            && pieces[0].is_king()
+            && pieces[1].is_knight()
+            && pieces[2].is_knight()
+        {
+            false
+        } else if pieces.len() == 3
+            && pieces[0].is_king()
+            && pieces[1].is_bishop()
+            && pieces[2].is_bishop()
+        {
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Could `color` ever checkmate with the material it has left, if the
+    /// other player helped?
+    ///
+    /// This follows the FIDE rule for losing on time: a player whose flag
+    /// falls only loses if the opponent has mating material. A lone king,
+    /// a king and knight, and a king with any number of bishops all on
+    /// squares of one color can never mate. Anything else can, including
+    /// two knights or bishops on both colors, even when mate can't be forced.
+    pub fn has_mating_material(&self, color: Color) -> bool {
+        let mut knights = 0;
+        let mut bishop_colors = vec![];
+        for square in &self.squares {
+            match square.get_piece() {
+                Some(piece) if piece.get_color() == color => match piece {
+                    Piece::King(..) => {}
+                    Piece::Knight(..) => knights += 1,
+                    Piece::Bishop(_, pos) => bishop_colors.push(pos.square_color()),
+                    _ => return true,
+                },
+                _ => {}
+            }
+        }
+
+        bishop_colors.sort();
+        bishop_colors.dedup();
+        !matches!((knights, bishop_colors.len()), (0, 0) | (1, 0) | (0, 1))
+    }
+
+    /// Does the respective player have insufficient material?
+    #[inline]
+    pub fn has_insufficient_material(&self, color: Color) -> bool {
+        !self.has_sufficient_material(color)
+    }
+
+    /// Is this position a textbook draw, whatever material is left?
+    ///
+    /// Only draws with a known proof are recognized, so a position this
+    /// doesn't catch may still be drawn. These are positions where neither
+    /// side has mating material, and the wrong rook pawn: a king, bishop
+    /// and pawns on one rook file against a lone king that has reached the
+    /// corner, when the bishop can't cover the promotion square.
+    pub fn is_known_draw(&self) -> bool {
+        (!self.has_mating_material(WHITE) && !self.has_mating_material(BLACK))
+            || self.is_wrong_rook_pawn_draw(WHITE)
+            || self.is_wrong_rook_pawn_draw(BLACK)
+    }
+
+    /// Is `color` stuck with the wrong bishop for its rook pawns against a
+    /// lone king in the corner?
+    fn is_wrong_rook_pawn_draw(&self, color: Color) -> bool {
+        let mut bishop_colors = vec![];
+        let mut pawn_files = vec![];
+        let mut defender = None;
+        for piece in self.squares.iter().filter_map(|square| square.get_piece()) {
+            match piece {
+                Piece::King(c, pos) if c != color => defender = Some(pos),
+                Piece::King(..) => {}
+                Piece::Bishop(c, pos) if c == color => bishop_colors.push(pos.square_color()),
+                Piece::Pawn(c, pos) if c == color => pawn_files.push(pos.get_col()),
+                _ => return false,
+            }
+        }
+        bishop_colors.sort();
+        bishop_colors.dedup();
+        pawn_files.sort();
+        pawn_files.dedup();
+
+        let (defender, bishop_color, file) = match (defender, bishop_colors.as_slice(), pawn_files.as_slice()) {
+            (Some(defender), [bishop_color], [file]) if *file == 0 || *file == 7 => {
+                (defender, *bishop_color, *file)
+            }
+            _ => return false,
+        };
+        let promotion = match color {
+            WHITE => Position::new(7, file),
+            BLACK => Position::new(0, file),
+        };
+        bishop_color != promotion.square_color()
+            && (defender.get_row() - promotion.get_row()).abs() <= 1
+            && (defender.get_col() - promotion.get_col()).abs() <= 1
+    }
+
+    /// Is the game drawn because neither player has enough material left?
+    ///
+    /// Both players need insufficient material by `has_insufficient_material`.
+    /// Bishops are the exception: when both colors of square have a bishop on
+    /// them, like a king and bishop each on opposite colors, mate is still
+    /// possible and the game goes on.
+    pub fn is_insufficient_material(&self) -> bool {
+        if self.has_sufficient_material(WHITE) || self.has_sufficient_material(BLACK) {
+            return false;
+        }
+        let mut bishop_colors = vec![];
+        let mut knights = 0;
+        for piece in self.squares.iter().filter_map(|square| square.get_piece()) {
+            match piece {
+                Piece::Bishop(_, pos) => bishop_colors.push(pos.square_color()),
+                Piece::Knight(..) => knights += 1,
+                _ => {}
+            }
+        }
+        bishop_colors.sort();
+        bishop_colors.dedup();
+        knights > 0 || bishop_colors.len() < 2
+    }
+
+    /// Is the current player in stalemate?
+    pub fn is_stalemate(&self) -> bool {
+        (self.get_legal_moves().is_empty() && !self.is_in_check(self.get_current_player_color()))
+            || self.is_insufficient_material()
+            || (self.halfmoves > 100)
+    }
+
+    /// Can the current player only move their king?
+    ///
+    /// In check, this means the check can't be blocked and the checking
+    /// piece can't be captured by anything but the king, which is a strong
+    /// sign that mate is close. Castling counts as a king move. This is
+    /// false when the current player has no legal moves at all.
+    pub fn only_king_moves(&self) -> bool {
+        let moves = self.get_legal_moves();
+        !moves.is_empty() && moves.iter().all(|m| self.is_king_move(*m))
+    }
+
+    /// Get the legal moves for the current player that don't move their
+    /// king, leaving out castling too.
+    ///
+    /// Together with the king's own moves, this tells whether a position
+    /// holds up when the king can't help, like in a fortress or zugzwang.
+    pub fn non_king_legal_moves(&self) -> Vec<Move> {
+        self.get_legal_moves()
+            .into_iter()
+            .filter(|m| !self.is_king_move(*m))
+            .collect()
+    }
+
+    /// Get the legal moves for the current player, sorted by `cmp`.
+    ///
+    /// The sort is stable, so moves that `cmp` considers equal stay in the
+    /// order `get_legal_moves` gives them.
+    pub fn legal_moves_sorted_by<F: Fn(&Move, &Move) -> Ordering>(&self, cmp: F) -> Vec<Move> {
+        let mut moves = self.get_legal_moves();
+        moves.sort_by(cmp);
+        moves
+    }
+
+    // castling counts as a king move
+    fn is_king_move(&self, m: Move) -> bool {
+        match m {
+            Move::KingSideCastle | Move::QueenSideCastle => true,
+            Move::Piece(from, _) => matches!(self.get_piece(from), Some(Piece::King(..))),
+            Move::Promotion(..) | Move::Resign => false,
+        }
+    }
+
+    /// Is the current player in checkmate?
+    pub fn is_checkmate(&self) -> bool {
+        self.is_in_check(self.get_current_player_color()) && self.get_legal_moves().is_empty()
+    }
+
+    /// Change the current turn to the next player.
+    #[inline]
+    pub fn change_turn(mut self) -> Self {
+        self.pass_turn();
+        self
+    }
+
+    #[inline]
+    fn pass_turn(&mut self) {
+        if self.turn == BLACK {
+            self.fullmoves += 1;
+        }
+        self.turn = !self.turn;
+    }
+
+    fn apply_move(&self, m: Move) -> Self {
+        if m == Move::Resign {
+            return self.remove_all(self.turn).queen_all(!self.turn);
+        }
+        let mut result = *self;
+        result.apply_move_in_place(m);
+        result
+    }
+
+    // apply a move to this board without passing the turn, and get what
+    // it changed; resigning changes nothing here
+    fn apply_move_in_place(&mut self, m: Move) -> MoveUndo {
+        let mut undo = MoveUndo {
+            m,
+            moved: None,
+            captured: None,
+            en_passant: self.en_passant,
+            white_castling_rights: self.white_castling_rights,
+            black_castling_rights: self.black_castling_rights,
+            turn: self.turn,
+            halfmoves: self.halfmoves,
+            fullmoves: self.fullmoves,
+        };
+
+        match m {
+            Move::KingSideCastle | Move::QueenSideCastle => {
+                let (king_from, king_to, rook_from, rook_to) = self.castling_squares(castle_side(m), self.turn);
+                if self.get_piece(king_from) == Some(Piece::King(self.turn, king_from)) {
+                    undo.moved = self.move_piece(king_from, king_to, None);
+                    self.move_piece(rook_from, rook_to, None);
+                    self.halfmoves += 1;
+                }
+            }
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => {
+                undo.captured = self.get_piece(to);
+                if let (Some(en_passant), Some(Piece::Pawn(player_color, _))) =
+                    (self.en_passant, self.get_piece(from))
+                {
+                    if (en_passant == from.pawn_up(player_color).next_left()
+                        || en_passant == from.pawn_up(player_color).next_right())
+                        && en_passant == to
+                    {
+                        let victim = en_passant.pawn_back(player_color);
+                        undo.captured = self.get_piece(victim);
+                        *self.get_square(victim) = EMPTY_SQUARE;
+                    }
+                }
+
+                let promotion = match m {
+                    Move::Promotion(_, _, promotion) => Some(promotion),
+                    _ => None,
+                };
+                undo.moved = self.move_piece(from, to, promotion);
+                // promoting is a pawn move, so it always resets the clock
+                let is_pawn_move = promotion.is_some() || undo.moved.is_some_and(|piece| piece.is_pawn());
+                if is_pawn_move || undo.captured.is_some() {
+                    self.halfmoves = 0;
+                } else {
+                    self.halfmoves += 1;
+                }
+            }
+            Move::Resign => {}
+        }
+        undo
+    }
+
+    /// Get the Zobrist hash of the position.
+    ///
+    /// Boards with the same pieces on the same squares, the same player to
+    /// move, the same castling rights and the same en passant square hash
+    /// the same. Like Polyglot, the en passant square only counts when a
+    /// pawn could actually capture on it, so transpositions that differ
+    /// only by an unusable en passant square hash the same. The move
+    /// counters aren't part of the hash.
+    pub fn zobrist(&self) -> u64 {
+        let mut hash = 0;
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                let pos = piece.get_pos();
+                hash ^= zobrist::PIECE_KEYS[zobrist::piece_index(piece)]
+                    [(pos.get_row() * 8 + pos.get_col()) as usize];
+            }
+        }
+
+        let rights = [
+            self.white_castling_rights.can_kingside_castle(),
+            self.white_castling_rights.can_queenside_castle(),
+            self.black_castling_rights.can_kingside_castle(),
+            self.black_castling_rights.can_queenside_castle(),
+        ];
+        for (right, key) in rights.iter().zip(&zobrist::CASTLING_KEYS) {
+            if *right {
+                hash ^= key;
+            }
+        }
+
+        if let Some(en_passant) = self.en_passant {
+            // the squares a pawn capturing onto the en passant square stands on
+            let capturers = Self::pawn_attacks(en_passant, !self.turn);
+            let can_capture = self.squares.iter().filter_map(|square| square.get_piece()).any(
+                |piece| piece == Piece::Pawn(self.turn, piece.get_pos())
+                    && capturers & square_bit(piece.get_pos()) != 0,
+            );
+            if can_capture {
+                hash ^= zobrist::EN_PASSANT_KEYS[en_passant.get_col() as usize];
+            }
+        }
+        if self.turn == BLACK {
+            hash ^= zobrist::BLACK_TO_MOVE_KEY;
+        }
+        hash
+    }
+
+    /// Count the leaf positions of the legal move tree `depth` plies deep.
+    ///
+    /// Comparing these counts with known values is the standard way to
+    /// check a move generator.
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        self.get_legal_moves()
+            .into_iter()
+            .map(|m| self.apply_eval_move(m).perft(depth - 1))
+            .sum()
+    }
+
+    /// Count the same positions as `perft`, split up by the first move
+    /// played, in the order `get_legal_moves` gives them.
+    ///
+    /// Comparing this against another engine's divided counts narrows a
+    /// wrong total down to the move that leads to it.
+    pub fn perft_divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return vec![];
+        }
+        self.get_legal_moves()
+            .into_iter()
+            .map(|m| (m, self.apply_eval_move(m).perft(depth - 1)))
+            .collect()
+    }
+
+    /// Count the same positions as `perft`, but remember the count for
+    /// every position and depth in `table`, so transpositions are only
+    /// counted once.
+    ///
+    /// Positions are keyed by their Zobrist hash, so a hash that collides
+    /// or misses part of the position makes this disagree with `perft`.
+    pub fn perft_hashed(&self, depth: u32, table: &mut DashMap<(u64, u32), u64>) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let key = (self.zobrist(), depth);
+        if let Some(count) = table.get(&key) {
+            return *count;
+        }
+
+        let count = self
+            .get_legal_moves()
+            .into_iter()
+            .map(|m| self.apply_eval_move(m).perft_hashed(depth - 1, table))
+            .sum();
+        table.insert(key, count);
+        count
+    }
+
+    /// Play random legal moves from this position until the game ends or
+    /// `max_plies` moves have been played.
+    ///
+    /// This returns every position visited in order, starting with this
+    /// one and ending with the position the game stopped in.
+    pub fn play_random_game(&self, rng: &mut Rng, max_plies: u32) -> Vec<Board> {
+        let mut result = vec![*self];
+        let mut board = *self;
+        let mut history = PositionHistory::default();
+        for _ in 0..max_plies {
+            let legal_moves = board.get_legal_moves();
+            if legal_moves.is_empty() {
+                break;
+            }
+            let m = legal_moves[rng.below(legal_moves.len())];
+            match board.play_move_with(m, &mut history) {
+                GameResult::Continuing(next_board) => {
+                    board = next_board;
+                    result.push(board);
+                }
+                _ => {
+                    result.push(board.apply_eval_move(m));
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// Apply a move for the current player, passing the turn, and get the
+    /// information needed to take it back with `revert`.
+    ///
+    /// The move is not checked for legality, like `apply_eval_move`.
+    pub fn with_move_applied(&self, m: Move) -> (Self, MoveUndo) {
+        let mut next = *self;
+        let undo = next.make_move(m);
+        (next, undo)
+    }
+
+    /// Apply a move for the current player to this board in place, passing
+    /// the turn, and get the information `revert` needs to take it back.
+    ///
+    /// This lets a search walk the move tree on a single board, touching
+    /// only the squares the move changes. The move is not checked for
+    /// legality, like `apply_eval_move`, but only legal moves are sure to be
+    /// taken back exactly. Resigning only passes the turn here.
+    pub fn make_move(&mut self, m: Move) -> MoveUndo {
+        let undo = self.apply_move_in_place(m);
+        self.pass_turn();
+        undo
+    }
+
+    /// Take back the move that `undo` came from, restoring the board to
+    /// exactly what it was before `make_move` or `with_move_applied`.
+    pub fn revert(&mut self, undo: MoveUndo) {
+        self.turn = undo.turn;
+        if let Some(moved) = undo.moved {
+            let to = match undo.m {
+                Move::KingSideCastle | Move::QueenSideCastle => {
+                    let (_, king_to, rook_from, rook_to) = self.castling_squares(castle_side(undo.m), self.turn);
+                    if let Some(rook) = self.get_piece(rook_to) {
+                        *self.get_square(rook_to) = EMPTY_SQUARE;
+                        self.add_piece(rook.move_to(rook_from));
+                    }
+                    king_to
+                }
+                Move::Piece(_, to) | Move::Promotion(_, to, _) => to,
+                Move::Resign => unreachable!("resigning doesn't move a piece"),
+            };
+            *self.get_square(to) = EMPTY_SQUARE;
+            self.add_piece(moved);
+        }
+        if let Some(captured) = undo.captured {
+            self.add_piece(captured);
+        }
+
+        self.en_passant = undo.en_passant;
+        self.white_castling_rights = undo.white_castling_rights;
+        self.black_castling_rights = undo.black_castling_rights;
+        self.halfmoves = undo.halfmoves;
+        self.fullmoves = undo.fullmoves;
+    }
+
+    /// Replay `moves` from this position, confirming that every one of
+    /// them is legal.
+    ///
+    /// This returns the result of each move in turn, so a line ending in
+    /// checkmate ends with a `GameResult::Victory`. If a move is illegal,
+    /// including any move after the game has ended, this returns its index
+    /// in `moves` and why it is illegal.
+    pub fn validate_line(&self, moves: &[Move]) -> Result<Vec<GameResult>, (usize, IllegalMoveReason)> {
+        let mut board = Some(*self);
+        let mut history = PositionHistory::default();
+        let mut results = Vec::with_capacity(moves.len());
+        for (i, m) in moves.iter().enumerate() {
+            let current = board.ok_or((i, IllegalMoveReason::GameOver))?;
+            let result = current.play_move_with(*m, &mut history);
+            board = match result {
+                GameResult::Continuing(next) => Some(next),
+                GameResult::Victory(_) | GameResult::Stalemate => None,
+                GameResult::IllegalMove(m) => return Err((i, current.illegal_move_reason(m))),
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    // why `play_move` rejected `m`
+    fn illegal_move_reason(&self, m: Move) -> IllegalMoveReason {
+        let (from, to) = match m {
+            Move::KingSideCastle => {
+                return IllegalMoveReason::Castle(self.castle_legality(CastleSide::KingSide))
+            }
+            Move::QueenSideCastle => {
+                return IllegalMoveReason::Castle(self.castle_legality(CastleSide::QueenSide))
+            }
+            Move::Piece(from, to) | Move::Promotion(from, to, _) => (from, to),
+            Move::Resign => unreachable!("resigning is always legal"),
+        };
+        let piece = match self.get_piece(from) {
+            Some(piece) => piece,
+            None => return IllegalMoveReason::EmptySquare,
+        };
+        if piece.get_color() != self.turn {
+            return IllegalMoveReason::NotYourPiece;
+        }
+        if let Move::Promotion(_, _, promotion) = m {
+            if !piece.is_pawn()
+                || (to.get_row() != 0 && to.get_row() != 7)
+                || promotion.is_king()
+                || promotion.is_pawn()
+            {
+                return IllegalMoveReason::InvalidPromotion;
+            }
+        }
+        if !piece.get_pseudo_legal_moves(self).contains(&Move::Piece(from, to)) {
+            return IllegalMoveReason::IllegalMovement;
+        }
+        IllegalMoveReason::LeavesKingInCheck
+    }
+
+    /// Does playing a move leave the other player without a legal move
+    /// while they aren't in check?
+    ///
+    /// When winning, such a move throws the win away for a draw, so the
+    /// search or a UI can use this to flag it.
+    pub fn move_causes_stalemate(&self, m: Move) -> bool {
+        let next = self.apply_eval_move(m);
+        next.get_legal_moves().is_empty() && !next.is_in_check(next.turn)
+    }
+
+    /// Get how the game stands after a move, without playing it.
+    ///
+    /// This is exactly what `play_move` returns, so a preview of whether a
+    /// move ends the game always agrees with what playing it does.
+    #[inline]
+    pub fn result_after(&self, m: Move) -> GameResult {
+        self.play_move(m)
+    }
+
+    /// Get the result of the game as it stands, without playing a move.
+    ///
+    /// The player to move has lost when they are checkmated. The game is a
+    /// `GameResult::Stalemate` when they have no legal moves otherwise, when
+    /// neither side has enough material to mate, or when the halfmove clock
+    /// has reached 100. Any other position is `GameResult::Continuing` with
+    /// this board.
+    pub fn status(&self) -> GameResult {
+        if self.is_checkmate() {
+            GameResult::Victory(!self.turn)
+        } else if self.is_stalemate() || self.can_claim_fifty_move() {
+            GameResult::Stalemate
+        } else {
+            GameResult::Continuing(*self)
+        }
+    }
+
+    /// Play a move given as a `Move` or as text, like `"e4"`, `"e2e4"` or
+    /// `"e2 to e4"`, and confirm it is legal.
+    ///
+    /// Text that can't be read as a move is an error, since there is no
+    /// move to put in a `GameResult::IllegalMove`. Once read, the move is
+    /// played with `play_move`.
+    pub fn play<T: Into<MoveInput>>(&self, input: T) -> Result<GameResult, String> {
+        let m = input.into().resolve(self)?;
+        Ok(self.play_move(m))
+    }
+
+    /// Play a move and confirm it is legal.
+    ///
+    /// A move that brings the halfmove clock to 100 ends the game in a
+    /// `GameResult::Stalemate` unless it checkmates. The board doesn't know
+    /// the positions before it, so use `play_move_with` to end the game on
+    /// a threefold repetition too.
+    pub fn play_move(&self, m: Move) -> GameResult {
+        let current_color = self.get_turn_color();
+
+        if m == Move::Resign {
+            GameResult::Victory(!current_color)
+        } else if self.is_legal_move(m, current_color) {
+            self.apply_eval_move(m).status()
+        } else {
+            GameResult::IllegalMove(m)
+        }
+    }
+
+    /// Play a move like `play_move`, remembering the position in `history`.
+    ///
+    /// A move that reaches the same position for the third time since the
+    /// last capture or pawn move also ends the game in a
+    /// `GameResult::Stalemate`, unless it checkmates.
+    pub fn play_move_with(&self, m: Move, history: &mut PositionHistory) -> GameResult {
+        match self.play_move(m) {
+            GameResult::Continuing(next) => {
+                history.record(self, &next);
+                if history.is_threefold_repetition(&next) {
+                    GameResult::Stalemate
+                } else {
+                    GameResult::Continuing(next)
+                }
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::back_rank_mate;
+
+    fn castling_position(enemy: Piece) -> Board {
+        BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::Rook(WHITE, A1))
+            .piece(Piece::Rook(WHITE, H1))
+            .piece(Piece::King(BLACK, A8))
+            .piece(enemy)
+            .enable_kingside_castle(WHITE)
+            .enable_queenside_castle(WHITE)
+            .build()
+    }
+
+    #[test]
+    fn test_castle_legality() {
+        let board = castling_position(Piece::Pawn(BLACK, A7));
+        assert_eq!(board.castle_legality(CastleSide::KingSide), CastleLegality::Legal);
+        assert_eq!(board.castle_legality(CastleSide::QueenSide), CastleLegality::Legal);
+
+        // castling out of check
+        let board = castling_position(Piece::Rook(BLACK, E8));
+        assert_eq!(board.castle_legality(CastleSide::KingSide), CastleLegality::InCheck);
+        assert_eq!(board.castle_legality(CastleSide::QueenSide), CastleLegality::InCheck);
+
+        // castling through check
+        let board = castling_position(Piece::Rook(BLACK, F8));
+        assert_eq!(board.castle_legality(CastleSide::KingSide), CastleLegality::ThroughCheck);
+        let board = castling_position(Piece::Rook(BLACK, D8));
+        assert_eq!(board.castle_legality(CastleSide::QueenSide), CastleLegality::ThroughCheck);
+
+        // castling into check
+        let board = castling_position(Piece::Rook(BLACK, G8));
+        assert_eq!(board.castle_legality(CastleSide::KingSide), CastleLegality::IntoCheck);
+        let board = castling_position(Piece::Rook(BLACK, C8));
+        assert_eq!(board.castle_legality(CastleSide::QueenSide), CastleLegality::IntoCheck);
+
+        // the b-file only needs to be empty, not safe
+        let board = castling_position(Piece::Rook(BLACK, B8));
+        assert_eq!(board.castle_legality(CastleSide::QueenSide), CastleLegality::Legal);
+    }
+
+    #[test]
+    fn test_castle_legality_rights_and_path() {
+        let board = BoardBuilder::from(castling_position(Piece::Knight(WHITE, G1)))
+            .disable_queenside_castle(WHITE)
+            .build();
+        assert_eq!(board.castle_legality(CastleSide::KingSide), CastleLegality::PathBlocked);
+        assert_eq!(board.castle_legality(CastleSide::QueenSide), CastleLegality::RookMoved);
+
+        let board = BoardBuilder::from(board).disable_kingside_castle(WHITE).build();
+        assert_eq!(board.castle_legality(CastleSide::KingSide), CastleLegality::KingMoved);
+
+        let board = castling_position(Piece::Pawn(BLACK, A7)).set_turn(BLACK);
+        assert_eq!(board.castle_legality(CastleSide::KingSide), CastleLegality::KingMoved);
+    }
+
+    #[test]
+    fn test_no_castling_out_of_check() {
+        let board = castling_position(Piece::Rook(BLACK, E8));
+        let moves = board.get_legal_moves();
+        assert!(!moves.contains(&Move::KingSideCastle));
+        assert!(!moves.contains(&Move::QueenSideCastle));
+        assert!(!board.is_legal_move(Move::KingSideCastle, WHITE));
+        assert!(!board.is_legal_move(Move::QueenSideCastle, WHITE));
+    }
+
+    #[test]
+    fn test_castling_both_sides_generated() {
+        let moves = castling_position(Piece::Pawn(BLACK, A7)).get_legal_moves();
+        assert!(moves.contains(&Move::KingSideCastle));
+        assert!(moves.contains(&Move::QueenSideCastle));
+
+        // the queenside landing square must be safe too
+        let moves = castling_position(Piece::Rook(BLACK, C8)).get_legal_moves();
+        assert!(moves.contains(&Move::KingSideCastle));
+        assert!(!moves.contains(&Move::QueenSideCastle));
+    }
+
+    #[test]
+    fn test_is_in_check() {
+        // built without any history, and both kings are checked whoever is to move
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::King(BLACK, E8))
+            .piece(Piece::Bishop(BLACK, B4))
+            .piece(Piece::Rook(WHITE, A8))
+            .build();
+        assert!(board.is_in_check(WHITE));
+        assert!(board.is_in_check(BLACK));
+
+        let blocked = BoardBuilder::from(board)
+            .piece(Piece::Pawn(WHITE, D2))
+            .piece(Piece::Knight(BLACK, C8))
+            .build();
+        assert!(!blocked.is_in_check(WHITE));
+        assert!(!blocked.is_in_check(BLACK));
+
+        let no_white_king = Board::from_fen("4k3/8/8/8/1b6/8/8/8 w - - 0 1").unwrap();
+        assert!(!no_white_king.is_in_check(WHITE));
+        assert!(!Board::empty().is_in_check(BLACK));
+    }
+
+    #[test]
+    fn test_pieces_attacking() {
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, G1))
+            .piece(Piece::King(BLACK, G8))
+            .piece(Piece::Pawn(BLACK, D5))
+            .piece(Piece::Pawn(WHITE, E4))
+            .piece(Piece::Knight(WHITE, F4))
+            .piece(Piece::Rook(WHITE, D1))
+            .piece(Piece::Pawn(WHITE, D2))
+            .piece(Piece::Bishop(BLACK, A8))
+            .piece(Piece::Pawn(BLACK, C6))
+            .piece(Piece::Queen(BLACK, D8))
+            .piece(Piece::Knight(BLACK, F6))
+            .piece(Piece::Bishop(WHITE, H1))
+            .build();
+
+        // the rook, bishops and king are all blocked or out of reach
+        let mut attackers = board.pieces_attacking(D5);
+        attackers.sort();
+        assert_eq!(
+            attackers,
+            vec![
+                (E4, WHITE, Piece::Pawn(WHITE, E4)),
+                (F4, WHITE, Piece::Knight(WHITE, F4)),
+                (C6, BLACK, Piece::Pawn(BLACK, C6)),
+                (F6, BLACK, Piece::Knight(BLACK, F6)),
+                (D8, BLACK, Piece::Queen(BLACK, D8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_attackers() {
+        // the knight on d7 is pinned by the rook but still guards e5, the rook on
+        // e8 is blocked by the pawn on e6, and the pawn on f6 guards an empty square
+        let board = Board::from_fen("4r3/k2n3R/4pp2/8/4K3/8/8/8 w - - 0 1").unwrap();
+        let mut attackers = board.get_attackers(E5, BLACK);
+        attackers.sort();
+        assert_eq!(attackers, vec![F6, D7]);
+        assert!(board.set_turn(BLACK).moves_of_piece_at(D7).is_empty());
+        assert!(board.has_no_piece(E5));
+        assert_eq!(board.get_attackers(E5, WHITE), vec![E4]);
+        assert_eq!(board.get_attackers(E7, BLACK), vec![E8]);
+        assert_eq!(board.get_attackers(E7, WHITE), vec![H7]);
+        assert!(board.get_attackers(A1, WHITE).is_empty());
+    }
+
+    #[test]
+    fn test_material_key() {
+        let a = BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::Rook(WHITE, A1))
+            .piece(Piece::King(BLACK, E8))
+            .piece(Piece::Knight(BLACK, B8))
+            .build();
+        let b = BoardBuilder::default()
+            .piece(Piece::King(WHITE, G2))
+            .piece(Piece::Rook(WHITE, D4))
+            .piece(Piece::King(BLACK, A7))
+            .piece(Piece::Knight(BLACK, F5))
+            .set_turn(BLACK)
+            .build();
+        assert_eq!(a.material_key(), b.material_key());
+
+        // same pieces with the colors swapped
+        let c = BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::Knight(WHITE, B1))
+            .piece(Piece::King(BLACK, E8))
+            .piece(Piece::Rook(BLACK, A8))
+            .build();
+        assert_ne!(a.material_key(), c.material_key());
+        assert_ne!(Board::default().material_key(), a.material_key());
+        assert_ne!(Board::default().material_key(), Board::horde().material_key());
+        assert_eq!(Board::default().material_key(), Board::default().set_turn(BLACK).material_key());
+    }
+
+    #[test]
+    fn test_ascii_diagram() {
+        assert_eq!(
+            Board::default().to_ascii_diagram(),
+            "8 r n b q k b n r\n\
+             7 p p p p p p p p\n\
+             6 . . . . . . . .\n\
+             5 . . . . . . . .\n\
+             4 . . . . . . . .\n\
+             3 . . . . . . . .\n\
+             2 P P P P P P P P\n\
+             1 R N B Q K B N R\n\
+             \x20 a b c d e f g h\n"
+        );
+        // the board isn't flipped when it's black's turn
+        assert_eq!(
+            Board::default().set_turn(BLACK).to_ascii_diagram(),
+            Board::default().to_ascii_diagram()
+        );
+    }
+
+    #[test]
+    fn test_moves_of_piece_at() {
+        let sorted = |mut moves: Vec<Move>| {
+            moves.sort();
+            moves
+        };
+
+        let board = Board::default();
+        let knight = vec![Move::Piece(B1, A3), Move::Piece(B1, C3)];
+        assert_eq!(sorted(board.moves_of_piece_at(B1)), knight);
+        assert_eq!(sorted(board.pseudo_legal_moves_of_piece_at(B1)), knight);
+        assert_eq!(board.moves_of_piece_at(E4), vec![]);
+
+        // the bishop on d2 is pinned to its king by the bishop on b4
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::Bishop(WHITE, D2))
+            .piece(Piece::King(BLACK, E8))
+            .piece(Piece::Bishop(BLACK, B4))
+            .build();
+        assert_eq!(
+            sorted(board.pseudo_legal_moves_of_piece_at(D2)),
+            sorted(vec![
+                Move::Piece(D2, C1),
+                Move::Piece(D2, C3),
+                Move::Piece(D2, B4),
+                Move::Piece(D2, E3),
+                Move::Piece(D2, F4),
+                Move::Piece(D2, G5),
+                Move::Piece(D2, H6),
+            ])
+        );
+        assert_eq!(
+            sorted(board.moves_of_piece_at(D2)),
+            sorted(vec![Move::Piece(D2, C3), Move::Piece(D2, B4)])
+        );
+    }
+
+    fn bits(squares: &[Position]) -> u64 {
+        squares.iter().fold(0, |bits, pos| bits | square_bit(*pos))
+    }
+
+    #[test]
+    fn test_attack_maps() {
+        assert_eq!(
+            Board::knight_attacks(D4),
+            bits(&[C2, E2, B3, F3, B5, F5, C6, E6])
+        );
+        assert_eq!(Board::knight_attacks(A1), bits(&[B3, C2]));
+        assert_eq!(Board::knight_attacks(H5), bits(&[G3, F4, F6, G7]));
+
+        assert_eq!(
+            Board::king_attacks(E4),
+            bits(&[D3, E3, F3, D4, F4, D5, E5, F5])
+        );
+        assert_eq!(Board::king_attacks(H8), bits(&[G8, G7, H7]));
+
+        assert_eq!(Board::pawn_attacks(E4, WHITE), bits(&[D5, F5]));
+        assert_eq!(Board::pawn_attacks(E4, BLACK), bits(&[D3, F3]));
+        assert_eq!(Board::pawn_attacks(A2, WHITE), bits(&[B3]));
+        assert_eq!(Board::pawn_attacks(H7, BLACK), bits(&[G6]));
+
+        let board = BoardBuilder::default()
+            .piece(Piece::Rook(WHITE, D4))
+            .piece(Piece::Pawn(WHITE, D6))
+            .piece(Piece::Knight(BLACK, B4))
+            .piece(Piece::Bishop(WHITE, A1))
+            .piece(Piece::Pawn(BLACK, F6))
+            .build();
+        assert_eq!(
+            board.rook_attacks(D4),
+            bits(&[D1, D2, D3, D5, D6, C4, B4, E4, F4, G4, H4])
+        );
+        assert_eq!(board.attack_map(D4), board.rook_attacks(D4));
+        assert_eq!(board.bishop_attacks(A1), bits(&[B2, C3, D4]));
+        assert_eq!(
+            board.bishop_attacks(D4),
+            bits(&[C3, B2, A1, E3, F2, G1, C5, B6, A7, E5, F6])
+        );
+        assert_eq!(
+            board.queen_attacks(D4),
+            board.rook_attacks(D4) | board.bishop_attacks(D4)
+        );
+        assert_eq!(board.rook_attacks(H8).count_ones(), 14);
+        assert_eq!(board.attack_map(E4), 0);
+    }
+
+    #[test]
+    fn test_play_random_game() {
+        let mut rng = Rng::new(7);
+        // kings shut in behind locked pawns soon repeat a position
+        let locked = Board::from_fen("7k/5p1p/5P1P/8/8/p1p5/P1P5/K7 w - - 0 1").unwrap();
+        let starts = [(Board::default(), 0), (Board::default(), 30), (Board::default(), 400), (locked, 400)];
+        for (start, max_plies) in &starts {
+            let boards = start.play_random_game(&mut rng, *max_plies);
+            assert_eq!(boards[0], *start);
+            assert!(boards.len() as u32 <= max_plies + 1);
+
+            for pair in boards.windows(2) {
+                assert!(
+                    pair[0]
+                        .get_legal_moves()
+                        .into_iter()
+                        .any(|m| pair[0].apply_eval_move(m) == pair[1]),
+                    "{} can't be reached from {}",
+                    pair[1].fen(),
+                    pair[0].fen()
+                );
+            }
+
+            let last = boards.last().unwrap();
+            let repetitions = boards.iter().filter(|board| board.zobrist() == last.zobrist()).count();
+            assert!(
+                boards.len() as u32 == max_plies + 1
+                    || last.is_checkmate()
+                    || last.is_stalemate()
+                    || repetitions >= 3
+                    || last.can_claim_fifty_move(),
+                "{}",
+                last.fen()
+            );
+        }
+
+        // a game drawn by repetition ends on the repeated position
+        let boards = locked.play_random_game(&mut Rng::new(0), 400);
+        let last = boards.last().unwrap();
+        assert_eq!(boards.iter().filter(|board| board.zobrist() == last.zobrist()).count(), 3);
+        assert!(!last.is_stalemate());
+
+        // the same seed plays the same game
+        assert_eq!(
+            Board::default().play_random_game(&mut Rng::new(3), 20),
+            Board::default().play_random_game(&mut Rng::new(3), 20)
+        );
+    }
+
+    #[test]
+    fn test_promotion_distance() {
+        let board = Board::from_fen("7k/1p4P1/8/2p5/5P2/p7/P3P3/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.promotion_distance(G7), Some((1, false)));
+        assert_eq!(board.promotion_distance(F4), Some((4, false)));
+        assert_eq!(board.promotion_distance(E2), Some((6, false)));
+        assert_eq!(board.promotion_distance(B7), Some((6, false)));
+        assert_eq!(board.promotion_distance(C5), Some((4, false)));
+
+        // the pawns on the a-file stand in each other's way
+        assert_eq!(board.promotion_distance(A2), Some((6, true)));
+        assert_eq!(board.promotion_distance(A3), Some((2, true)));
+
+        assert_eq!(board.promotion_distance(E1), None);
+        assert_eq!(board.promotion_distance(D4), None);
+    }
+
+    #[test]
+    fn test_is_passed_pawn() {
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::King(BLACK, E8))
+            .piece(Piece::Pawn(WHITE, D4))
+            .piece(Piece::Pawn(BLACK, E6))
+            .piece(Piece::Pawn(WHITE, G5))
+            .piece(Piece::Pawn(BLACK, F4))
+            .piece(Piece::Pawn(WHITE, A5))
+            .piece(Piece::Pawn(BLACK, B7))
+            .piece(Piece::Pawn(WHITE, H2))
+            .piece(Piece::Pawn(BLACK, A3))
+            .build();
+
+        // an enemy pawn ahead on an adjacent file
+        assert!(!board.is_passed_pawn(D4));
+        assert!(!board.is_passed_pawn(E6));
+        // enemy pawns beside or behind don't matter
+        assert!(board.is_passed_pawn(G5));
+        assert!(board.is_passed_pawn(F4));
+        // on the a-file only the b-file is adjacent
+        assert!(!board.is_passed_pawn(A5));
+        assert!(board.is_passed_pawn(A3));
+        assert!(board.is_passed_pawn(H2));
+        assert!(!board.is_passed_pawn(B7));
+
+        assert!(!board.is_passed_pawn(E1));
+        assert!(!board.is_passed_pawn(C3));
+    }
+
+    #[test]
+    fn test_king_square() {
+        let board = castling_position(Piece::Pawn(BLACK, A7));
+        assert_eq!(board.king_square(WHITE), Some(E1));
+        assert_eq!(board.king_square(BLACK), Some(A8));
+
+        let board = match board.play_move(Move::KingSideCastle) {
+            GameResult::Continuing(board) => board,
+            _ => panic!("O-O failed"),
+        };
+        assert_eq!(board.king_square(WHITE), Some(G1));
+
+        let board = match board.play_move(Move::Piece(A8, B8)) {
+            GameResult::Continuing(board) => board,
+            _ => panic!("Kb8 failed"),
+        };
+        assert_eq!(board.king_square(BLACK), Some(B8));
+
+        let board = castling_position(Piece::Pawn(BLACK, A7));
+        let board = match board.play_move(Move::QueenSideCastle) {
+            GameResult::Continuing(board) => board,
+            _ => panic!("O-O-O failed"),
+        };
+        assert_eq!(board.king_square(WHITE), Some(C1));
+        assert_eq!(board.get_king_pos(WHITE), Some(C1));
+
+        assert_eq!(BoardBuilder::default().build().king_square(WHITE), None);
+    }
+
+    #[test]
+    fn test_en_passant_pin() {
+        // after ...d7-d5, exd6 would take both pawns off the fifth rank
+        // and leave the king on b5 in check from the rook on h5
+        let pinned = BoardBuilder::default()
+            .piece(Piece::King(WHITE, B5))
+            .piece(Piece::Pawn(WHITE, E5))
+            .piece(Piece::Pawn(BLACK, D5))
+            .piece(Piece::Rook(BLACK, H5))
+            .piece(Piece::King(BLACK, H8))
+            .set_en_passant(Some(D6))
+            .build();
+        assert!(!pinned.get_legal_moves().contains(&Move::Piece(E5, D6)));
+        assert_eq!(
+            pinned.play_move(Move::Piece(E5, D6)),
+            GameResult::IllegalMove(Move::Piece(E5, D6))
+        );
+
+        let free = BoardBuilder::from(pinned)
+            .piece(Piece::Pawn(WHITE, G5))
+            .build();
+        assert!(free.get_legal_moves().contains(&Move::Piece(E5, D6)));
+        // the en passant square only allows capturing onto it
+        for to in &[E7, E8, C6, A1] {
+            assert_eq!(
+                free.play_move(Move::Piece(E5, *to)),
+                GameResult::IllegalMove(Move::Piece(E5, *to))
+            );
+        }
+    }
+
+    #[test]
+    fn test_best_capture() {
+        assert_eq!(Board::default().best_capture(), None);
+
+        // the knight on c6 is defended, the rook on g5 hangs
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, H1))
+            .piece(Piece::Queen(WHITE, D2))
+            .piece(Piece::Bishop(WHITE, A4))
+            .piece(Piece::King(BLACK, G8))
+            .piece(Piece::Knight(BLACK, C6))
+            .piece(Piece::Pawn(BLACK, B7))
+            .piece(Piece::Rook(BLACK, G5))
+            .build();
+        assert_eq!(board.best_capture(), Some((Move::Piece(D2, G5), 500)));
+
+        // only losing captures are left
+        let board = BoardBuilder::from(board)
+            .piece(Piece::Pawn(BLACK, H6))
+            .build();
+        assert_eq!(board.best_capture(), Some((Move::Piece(A4, C6), 0)));
+    }
+
+    #[test]
+    fn test_see_pawns() {
+        // the rook wins a pawn and loses itself to the pawn on c6
+        let board = Board::from_fen("4k3/8/2p5/3pp3/8/8/8/3RK3 w - - 0 1").unwrap();
+        assert_eq!(board.see_pawns(Move::Piece(D1, D5)), 1.0 - 5.0);
+        assert_eq!(board.see_pawns(Move::Piece(D1, D5)), board.see(Move::Piece(D1, D5)) as f64 / 100.0);
+
+        let undefended = Board::from_fen("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1").unwrap();
+        assert_eq!(undefended.see_pawns(Move::Piece(D1, D5)), 1.0);
+
+        // a quiet move is worth nothing, even onto a defended square
+        assert_eq!(board.see_pawns(Move::Piece(D1, D4)), 0.0);
+        assert_eq!(board.see(Move::Piece(D1, D4)), 0);
+    }
+
+    #[test]
+    fn test_perft() {
+        let board = Board::default();
+        assert_eq!(board.perft(0), 1);
+        assert_eq!(board.perft(1), 20);
+        assert_eq!(board.perft(2), 400);
+        assert_eq!(board.perft(3), 8902);
+        assert_eq!(board.perft(4), 197_281);
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let board = Board::default();
+        assert!(board.perft_divide(0).is_empty());
+
+        let divided = board.perft_divide(3);
+        assert_eq!(divided.len(), 20);
+        assert_eq!(divided.iter().map(|(_, count)| count).sum::<u64>(), board.perft(3));
+        assert!(divided.contains(&(Move::Piece(E2, E4), 600)));
+        assert!(divided.contains(&(Move::Piece(G1, F3), 440)));
+
+        // kiwipete, where castling and en passant matter
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let divided = board.perft_divide(2);
+        assert_eq!(divided.len(), 48);
+        assert_eq!(divided.iter().map(|(_, count)| count).sum::<u64>(), 2039);
+        assert!(divided.contains(&(Move::KingSideCastle, 43)));
+        assert!(divided.contains(&(Move::QueenSideCastle, 43)));
+    }
+
+    /// Check `evasion_moves` against the full move generator at every
+    /// position in check within `depth` plies, and count those positions.
+    fn check_evasions(board: &Board, depth: u32) -> usize {
+        let mut checks = 0;
+        if board.is_in_check(board.get_turn_color()) {
+            let mut evasions = board.evasion_moves();
+            let mut legal = board.get_legal_moves_for(board.get_turn_color());
+            evasions.sort();
+            legal.sort();
+            assert_eq!(evasions, legal, "{}", board.fen());
+            checks += 1;
+        } else {
+            assert!(board.evasion_moves().is_empty());
+        }
+        if depth > 0 {
+            for m in board.get_legal_moves() {
+                checks += check_evasions(&board.apply_eval_move(m), depth - 1);
+            }
+        }
+        checks
+    }
+
+    #[test]
+    fn test_evasion_moves() {
+        let positions = [
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(),
+            Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(),
+            Board::from_fen("4k3/8/8/8/1b6/8/3N4/R3K2R w KQ - 0 1").unwrap(),
+            Board::from_fen("4k3/8/8/2pP4/1K6/8/8/8 w - c6 0 1").unwrap(),
+        ];
+        let checks: usize = positions.iter().map(|board| check_evasions(board, 3)).sum();
+        assert!(checks > 1000, "{}", checks);
+
+        // in double check Bxd3 takes a checker but leaves the rook's check
+        let board = Board::from_fen("4r1k1/8/8/8/8/3n4/8/R3KB1R w KQ - 0 1").unwrap();
+        assert_eq!(board.evasion_moves(), vec![Move::Piece(E1, D1), Move::Piece(E1, D2)]);
+    }
+
+    // compare `perft_hashed` with `perft` for every depth in `depths`,
+    // sharing one table between the depths of each position
+    fn check_perft_hashed(depths: core::ops::RangeInclusive<u32>) {
+        let positions = [
+            Board::default(),
+            castling_position(Piece::Rook(BLACK, D8)),
+            Board::from_fen("4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap(),
+            Board::from_fen("r3k2r/pp3ppp/2n5/3qp3/3P4/2N2N2/PP3PPP/R2Q1RK1 b kq - 0 1").unwrap(),
+        ];
+        for board in &positions {
+            let mut table = DashMap::new();
+            for depth in depths.clone() {
+                assert_eq!(board.perft_hashed(depth, &mut table), board.perft(depth), "{}", board.fen());
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_hashed() {
+        check_perft_hashed(1..=3);
+    }
+
+    #[test]
+    #[ignore = "slow; run with `cargo test --release -- --ignored`"]
+    fn test_perft_hashed_deep() {
+        check_perft_hashed(1..=5);
+    }
+
+    #[test]
+    fn test_zobrist() {
+        let board = Board::default();
+        assert_ne!(board.zobrist(), board.set_turn(BLACK).zobrist());
+        assert_ne!(
+            board.zobrist(),
+            BoardBuilder::from(board).disable_kingside_castle(BLACK).build().zobrist()
+        );
+
+        // the same position reached by different move orders
+        let play = |moves: &[Move]| {
+            moves.iter().fold(Board::default(), |board, m| board.apply_eval_move(*m))
+        };
+        let a = play(&[Move::Piece(G1, F3), Move::Piece(G8, F6), Move::Piece(B1, C3)]);
+        let b = play(&[Move::Piece(B1, C3), Move::Piece(G8, F6), Move::Piece(G1, F3)]);
+        assert_eq!(a.zobrist(), b.zobrist());
+        assert_ne!(a.zobrist(), board.zobrist());
+
+        // 1.e4 e5 2.Nf3 and 1.Nf3 e5 2.e4, where only the second has an
+        // en passant square, which no black pawn can use
+        let a = play(&[Move::Piece(E2, E4), Move::Piece(E7, E5), Move::Piece(G1, F3)]);
+        let b = play(&[Move::Piece(G1, F3), Move::Piece(E7, E5), Move::Piece(E2, E4)]);
+        assert_ne!(a.get_en_passant(), b.get_en_passant());
+        assert_eq!(a.zobrist(), b.zobrist());
+        assert_eq!(a.zobrist_hash(), b.zobrist_hash());
+
+        // an en passant square that can be used does count
+        let usable = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let unusable = Board::from_fen("4k3/8/8/3p3P/8/8/8/4K3 w - d6 0 2").unwrap();
+        assert_ne!(usable.zobrist(), Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 2").unwrap().zobrist());
+        assert_eq!(unusable.zobrist(), Board::from_fen("4k3/8/8/3p3P/8/8/8/4K3 w - - 0 2").unwrap().zobrist());
+    }
+
+    #[test]
+    fn test_legal_moves_grouped() {
+        let board = Board::default();
+        let grouped = board.legal_moves_grouped();
+        assert_eq!(grouped.len(), 10);
+        assert_eq!(grouped[&G1], vec![Move::Piece(G1, F3), Move::Piece(G1, H3)]);
+
+        let mut flattened = grouped.values().flatten().copied().collect::<Vec<_>>();
+        let mut legal_moves = board.get_legal_moves();
+        flattened.sort();
+        legal_moves.sort();
+        assert_eq!(flattened, legal_moves);
+
+        let grouped = castling_position(Piece::Pawn(BLACK, A7)).legal_moves_grouped();
+        assert!(grouped[&E1].contains(&Move::KingSideCastle));
+        assert!(grouped[&E1].contains(&Move::QueenSideCastle));
+    }
+
+    #[test]
+    fn test_capture_count() {
+        let closed = Board::from_fen("4k3/8/8/1p1p1p2/1P1P1P2/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(closed.capture_count(), 0);
+
+        let boards = Board::default().play_random_game(&mut Rng::new(11), 60);
+        assert!(boards.iter().any(|board| board.capture_count() > 0));
+        for board in boards {
+            assert_eq!(board.capture_count(), board.get_capture_moves().len(), "{}", board.fen());
+        }
+    }
+
+    #[test]
+    fn test_taper() {
+        let start = Board::default();
+        assert_eq!(start.phase(), MAX_PHASE);
+        assert_eq!(start.taper(120, -40), 120);
+
+        let pawns = Board::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(pawns.phase(), 0);
+        assert_eq!(pawns.taper(120, -40), -40);
+
+        // a rook and a knight each: phase 6 of 24
+        let middling = Board::from_fen("3rk1n1/8/8/8/8/8/8/1N1RK3 w - - 0 1").unwrap();
+        assert_eq!(middling.phase(), 6);
+        assert_eq!(middling.taper(120, -40), 0);
+        assert_eq!(middling.taper(100, 200), 175);
+
+        // extra queens don't go past the midgame
+        let queens = Board::from_fen("qqqqk3/8/8/8/8/8/8/QQQQK3 w - - 0 1").unwrap();
+        assert_eq!(queens.phase(), MAX_PHASE);
+    }
+
+    #[test]
+    fn test_non_king_legal_moves() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/6P1/4K2R w K - 0 1").unwrap();
+        let legal = board.get_legal_moves();
+        assert!(legal.contains(&Move::KingSideCastle));
+
+        let mut moves = board.non_king_legal_moves();
+        moves.sort();
+        let mut expected: Vec<Move> = legal
+            .into_iter()
+            .filter(|m| matches!(m, Move::Piece(from, _) if *from == G2 || *from == H1))
+            .collect();
+        expected.sort();
+        assert_eq!(moves, expected);
+        assert_eq!(moves.len(), 2 + 7 + 2);
+
+        // a lone king has nothing else to move
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 0 1").unwrap().non_king_legal_moves().is_empty());
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        // both bishops on dark squares can never mate
+        let same_color = Board::from_fen("4k3/8/8/2b5/8/8/8/2B1K3 w - - 0 1").unwrap();
+        assert!(same_color.is_insufficient_material());
+        assert!(same_color.is_stalemate());
+        let opposite_colors = Board::from_fen("4k3/8/8/2b5/8/8/8/3BK3 w - - 0 1").unwrap();
+        assert!(!opposite_colors.is_insufficient_material());
+        assert!(!opposite_colors.is_stalemate());
+
+        // two bishops on different colors can mate a lone king, but not two on one color
+        assert!(!Board::from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap().is_insufficient_material());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/3B4/2B1K3 w - - 0 1").unwrap().is_insufficient_material());
+
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/3NK3 w - - 0 1").unwrap().is_insufficient_material());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap().is_insufficient_material());
+        assert!(!Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap().is_insufficient_material());
+        assert!(!Board::default().is_insufficient_material());
+
+        // taking the last knight leaves bishops on the same color
+        let board = Board::from_fen("4k3/8/8/2b5/8/4N3/8/2B1K3 b - - 0 1").unwrap();
+        assert!(!board.is_insufficient_material());
+        assert_eq!(board.play_move(Move::Piece(C5, E3)), GameResult::Stalemate);
+    }
+
+    #[test]
+    fn test_only_king_moves() {
+        let rook_check = Board::from_fen("4k3/8/8/8/8/8/8/K3R3 b - - 0 1").unwrap();
+        assert!(rook_check.only_king_moves());
+
+        // the rook on a2 can block on e2
+        let blockable = Board::from_fen("4k3/8/8/8/8/8/r7/K3R3 b - - 0 1").unwrap();
+        assert!(!blockable.only_king_moves());
+        assert!(blockable.get_legal_moves().contains(&Move::Piece(A2, E2)));
+
+        assert!(!Board::default().only_king_moves());
+        let mated = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert!(!mated.only_king_moves());
+    }
+
+    #[test]
+    fn test_validate_line() {
+        let fools_mate = [
+            Move::Piece(F2, F3),
+            Move::Piece(E7, E5),
+            Move::Piece(G2, G4),
+            Move::Piece(D8, H4),
+        ];
+        let results = Board::default().validate_line(&fools_mate).unwrap();
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(|r| matches!(r, GameResult::Continuing(_))));
+        assert_eq!(results[3], GameResult::Victory(BLACK));
+
+        let mut too_long = fools_mate.to_vec();
+        too_long.push(Move::Piece(A2, A3));
+        assert_eq!(
+            Board::default().validate_line(&too_long),
+            Err((4, IllegalMoveReason::GameOver))
+        );
+
+        let line = |moves: &[Move]| Board::default().validate_line(moves).unwrap_err();
+        assert_eq!(line(&[Move::Piece(E3, E4)]), (0, IllegalMoveReason::EmptySquare));
+        assert_eq!(line(&[Move::Piece(E7, E5)]), (0, IllegalMoveReason::NotYourPiece));
+        assert_eq!(
+            line(&[Move::Piece(E2, E4), Move::Piece(E7, E5), Move::Piece(E4, E5)]),
+            (2, IllegalMoveReason::IllegalMovement)
+        );
+        assert_eq!(
+            line(&[Move::Promotion(E2, E4, Piece::Queen(WHITE, E4))]),
+            (0, IllegalMoveReason::InvalidPromotion)
+        );
+        assert_eq!(
+            line(&[Move::KingSideCastle]),
+            (0, IllegalMoveReason::Castle(CastleLegality::PathBlocked))
+        );
+
+        let pinned = Board::from_fen("4r1k1/8/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            pinned.validate_line(&[Move::Piece(E2, D3)]),
+            Err((0, IllegalMoveReason::LeavesKingInCheck))
+        );
+    }
+
+    #[test]
+    fn test_needs_promotion_choice() {
+        let board = Board::from_fen("1n2k3/P6P/8/8/8/4p3/8/R3K3 w - - 0 1").unwrap();
+        assert!(board.needs_promotion_choice(A7, A8));
+        assert!(board.needs_promotion_choice(A7, B8));
+        assert!(board.needs_promotion_choice(H7, H8));
+        // a pawn that can't get there, a pawn off the last rank and a rook
+        assert!(!board.needs_promotion_choice(H7, G8));
+        assert!(!board.needs_promotion_choice(E3, E2));
+        assert!(!board.needs_promotion_choice(A1, A6));
+        assert!(!board.needs_promotion_choice(A1, A8));
+
+        // the black pawn promotes on the first rank, but only on its turn
+        let board = Board::from_fen("4k3/8/8/8/8/8/4p3/K7 b - - 0 1").unwrap();
+        assert!(board.needs_promotion_choice(E2, E1));
+        assert!(!board.set_turn(WHITE).needs_promotion_choice(E2, E1));
+    }
+
+    #[test]
+    fn test_expand() {
+        let boards = Board::default().play_random_game(&mut Rng::new(3), 30);
+        for board in boards {
+            let children = board.expand();
+            assert_eq!(children.len(), board.get_legal_moves().len());
+            for (m, child) in children {
+                assert_eq!(child, board.apply_eval_move(m));
+                assert_eq!(child.get_turn_color(), !board.get_turn_color());
+            }
+        }
+    }
+
+    #[test]
+    fn test_piece_mobility() {
+        let board = Board::from_fen("4k3/8/8/8/3N4/8/1P6/B3K3 w - - 0 1").unwrap();
+        assert_eq!(board.piece_mobility(D4), 8);
+        // boxed in by its own pawn
+        assert_eq!(board.piece_mobility(A1), 0);
+        assert_eq!(board.piece_mobility(B2), 2);
+        // not black's turn, but the king still has its moves
+        assert_eq!(board.piece_mobility(E8), 5);
+        assert_eq!(board.piece_mobility(E4), 0);
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/B3K3 w - - 0 1").unwrap();
+        assert_eq!(board.piece_mobility(A1), 7);
+    }
+
+    #[test]
+    fn test_fen_placement() {
+        assert_eq!(
+            Board::default().fen_placement(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"
+        );
+        let fen = "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.fen_placement(), "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R");
+        assert!(board.fen().starts_with(&(board.fen_placement() + " ")));
+    }
+
+    #[test]
+    fn test_move_to_san() {
+        let board = Board::default();
+        assert_eq!(board.move_to_san(Move::Piece(E2, E4)), "e4");
+        assert_eq!(board.move_to_san(Move::Piece(G1, F3)), "Nf3");
+
+        // both rooks reach d4, and both a-file rooks reach a2
+        let board = Board::from_fen("4k3/8/8/8/R6R/8/8/R3K3 w Q - 0 1").unwrap();
+        assert_eq!(board.move_to_san(Move::Piece(A4, D4)), "Rad4");
+        assert_eq!(board.move_to_san(Move::Piece(H4, D4)), "Rhd4");
+        assert_eq!(board.move_to_san(Move::Piece(A4, A2)), "R4a2");
+        assert_eq!(board.move_to_san(Move::Piece(A1, A2)), "R1a2");
+        assert_eq!(board.move_to_san(Move::Piece(A4, B4)), "Rab4");
+        assert_eq!(board.move_to_san(Move::Piece(A4, A8)), "Ra8+");
+        assert!(board.get_legal_moves().contains(&Move::QueenSideCastle));
+        assert_eq!(board.move_to_san(Move::QueenSideCastle), "O-O-O");
+
+        let board = Board::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.move_to_san(Move::Piece(A7, B8)), "axb8=Q+");
+        assert_eq!(board.move_to_san(Move::Piece(A7, A8)), "a8=Q");
+        assert_eq!(board.move_to_san(Move::Promotion(A7, A8, Piece::Knight(WHITE, A8))), "a8=N");
+
+        let board = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2").unwrap();
+        assert_eq!(board.move_to_san(Move::Piece(D8, H4)), "Qh4#");
+    }
+
+    #[test]
+    fn test_san_map() {
+        let boards = Board::default().play_random_game(&mut Rng::new(8), 40);
+        for board in boards {
+            let map = board.san_map();
+            assert_eq!(map.len(), board.get_legal_moves().len());
+            for (m, san) in map {
+                assert_eq!(san, board.move_to_san(m));
+            }
+        }
+    }
+
+    #[test]
+    fn test_evaluate_with_trace() {
+        // taking the undefended knight is worth it even after the reply
+        let board = Board::from_fen("4k3/3r4/8/8/3N4/8/8/4K3 b - - 0 1").unwrap();
+        let (line, components) = board.evaluate_with_trace(2, None);
+        assert_eq!(line.len(), 3);
+        assert_eq!(line[0], Move::Piece(D7, D4));
+        assert_eq!(line, board.principal_variation(2, None));
+        assert!(board.validate_line(&line).is_ok());
+
+        let leaf = line.iter().fold(board, |board, m| board.apply_eval_move(*m));
+        assert_eq!(components, leaf.eval_components(BLACK));
+        assert!(components[2] > board.eval_components(BLACK)[2]);
+
+        // the line stops when the game does
+        let mate_in_one = back_rank_mate();
+        let (line, _) = mate_in_one.evaluate_with_trace(3, None);
+        assert_eq!(line, vec![Move::Piece(A1, A8)]);
+    }
+
+    #[test]
+    fn test_legal_moves_sorted_by() {
+        let board = Board::from_fen("4k3/1P6/8/3q4/2P1r3/2N5/8/7K w - - 0 1").unwrap();
+        let moves = board.legal_moves_sorted_by(|a, b| {
+            board.captured_value_cp(*b).cmp(&board.captured_value_cp(*a))
+        });
+        let mut legal = board.get_legal_moves();
+        assert_eq!(moves.len(), legal.len());
+
+        // the queen captures come first, then the rook captures, then the rest
+        let captures = moves.iter().take_while(|m| board.is_capture(**m)).count();
+        assert_eq!(captures, legal.iter().filter(|m| board.is_capture(**m)).count());
+        assert!(captures >= 3);
+        assert!(moves[..captures].windows(2).all(|pair| {
+            board.captured_value_cp(pair[0]) >= board.captured_value_cp(pair[1])
+        }));
+        assert!(matches!(moves[0], Move::Piece(_, D5)), "{}", moves[0]);
+
+        // equal moves keep the generator's order
+        assert_eq!(board.legal_moves_sorted_by(|_, _| Ordering::Equal), legal);
+        let by_san = board.legal_moves_sorted_by(|a, b| board.move_to_san(*a).cmp(&board.move_to_san(*b)));
+        legal.sort_by_key(|m| board.move_to_san(*m));
+        assert_eq!(by_san, legal);
+    }
+
+    #[test]
+    fn test_status() {
+        let board = Board::default();
+        assert_eq!(board.status(), GameResult::Continuing(board));
+
+        // fool's mate
+        let board = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(board.status(), GameResult::Victory(BLACK));
+
+        let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(board.status(), GameResult::Stalemate);
+        let board = Board::from_fen("7k/8/6K1/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(board.status(), GameResult::Stalemate);
+        let board = Board::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 100 80").unwrap();
+        assert_eq!(board.status(), GameResult::Stalemate);
+        let board = Board::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 99 80").unwrap();
+        assert_eq!(board.status(), GameResult::Continuing(board));
+
+        // the status of the board a move reaches is what playing it gives
+        let board = back_rank_mate();
+        for m in board.get_legal_moves() {
+            assert_eq!(board.play_move(m), board.apply_eval_move(m).status(), "{}", m);
+        }
+    }
+
+    #[test]
+    fn test_play() {
+        let board = Board::default();
+        let expected = match board.play_move(Move::Piece(E2, E4)) {
+            GameResult::Continuing(next) => next,
+            other => panic!("{:?}", other),
+        };
+        for input in [
+            MoveInput::from("e4"),
+            MoveInput::from("e2e4"),
+            MoveInput::from("e2 to e4"),
+            MoveInput::from(Move::Piece(E2, E4)),
+        ] {
+            assert_eq!(board.play(input.clone()), Ok(GameResult::Continuing(expected)), "{:?}", input);
+        }
+        assert_eq!(board.play(String::from(" Nf3 ")), board.play(Move::Piece(G1, F3)));
+
+        // UCI castling is read as castling, not as a king move
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(MoveInput::from("e1g1").resolve(&board), Ok(Move::KingSideCastle));
+        assert_eq!(MoveInput::from("O-O-O").resolve(&board), Ok(Move::QueenSideCastle));
+
+        assert_eq!(
+            Board::default().play("e2e5"),
+            Ok(GameResult::IllegalMove(Move::Piece(E2, E5)))
+        );
+        assert!(Board::default().play("Ke2").is_err());
+        assert!(Board::default().play("hello").is_err());
+    }
+
+    #[test]
+    fn test_safety_map() {
+        // the queen on d5 and the e6 pawn attack each other with nothing
+        // defending either, while the f2 pawn is defended by the king and
+        // the rook
+        let board = Board::from_fen("4k3/8/4p3/3Q4/8/8/5P2/5RK1 w - - 0 1").unwrap();
+        let map = board.safety_map();
+        let at = |pos: Position| map[(pos.get_row() * 8 + pos.get_col()) as usize];
+        assert_eq!(at(D5), -1);
+        assert_eq!(at(F2), 2);
+        assert_eq!(at(E6), -1);
+        assert_eq!(at(E4), EMPTY_SAFETY);
+        assert_eq!(map.iter().filter(|safety| **safety != EMPTY_SAFETY).count(), 6);
+
+        // every square of the starting position is defended or empty
+        let map = Board::default().safety_map();
+        assert!(map.iter().all(|safety| *safety == EMPTY_SAFETY || *safety >= 0));
+        assert_eq!(map[(E2.get_row() * 8 + E2.get_col()) as usize], 4);
+    }
+
+    #[test]
+    fn test_castling_squares() {
+        let board = Board::default();
+        assert_eq!(board.castling_squares(CastleSide::KingSide, WHITE), (E1, G1, H1, F1));
+        assert_eq!(board.castling_squares(CastleSide::QueenSide, WHITE), (E1, C1, A1, D1));
+        assert_eq!(board.castling_squares(CastleSide::KingSide, BLACK), (E8, G8, H8, F8));
+        assert_eq!(board.castling_squares(CastleSide::QueenSide, BLACK), (E8, C8, A8, D8));
+
+        // castling puts the pieces on exactly those squares
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for (side, m) in [
+            (CastleSide::KingSide, Move::KingSideCastle),
+            (CastleSide::QueenSide, Move::QueenSideCastle),
+        ] {
+            let (king_from, king_to, rook_from, rook_to) = board.castling_squares(side, WHITE);
+            let after = board.apply_eval_move(m);
+            assert_eq!(after.get_piece(king_from), None);
+            assert_eq!(after.get_piece(rook_from), None);
+            assert_eq!(after.get_piece(king_to), Some(Piece::King(WHITE, king_to)));
+            assert_eq!(after.get_piece(rook_to), Some(Piece::Rook(WHITE, rook_to)));
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        // black wins the knight, but white's king and pawns stand better
+        let board = Board::from_fen("4k3/3r4/8/8/3N4/6P1/5PKP/8 b - - 0 1").unwrap();
+        let result = board.analyze(2, None);
+        assert_eq!(board.search(2, None).split, None);
+        assert_eq!(result.best_move, Move::Piece(D7, D4));
+
+        let split = result.split.unwrap();
+        let (line, _) = board.evaluate_with_trace(2, None);
+        let leaf = line.iter().fold(board, |board, m| board.apply_eval_move(*m));
+        assert_eq!(split.total(), leaf.static_eval_cp(BLACK, None));
+        assert_eq!(Score::Cp(split.total()), result.score);
+        assert_eq!(split.material, 200);
+        assert!(split.positional < 0);
+
+        // every term lands in exactly one of the two parts
+        let every_term = EvalEngine {
+            trapped_pieces: 1.0,
+            material_imbalance: 1.0,
+            rook_behind_passer: 1.0,
+            development: 1.0,
+            ..EvalEngine::from([1.0; 6])
+        };
+        for engine in [EvalEngine::default(), every_term] {
+            for color in [WHITE, BLACK] {
+                let (material, positional) = leaf.material_and_positional_split(color, engine);
+                let eval = leaf.static_eval(color, engine);
+                assert!((material + positional - eval).abs() < 1e-9, "{} + {} != {}", material, positional, eval);
+            }
+        }
+        let (material, _) = leaf.material_and_positional_split(BLACK, EvalEngine::from([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]));
+        assert_eq!(material, leaf.naive_value_for(BLACK));
+    }
+
+    #[test]
+    fn test_best_threat() {
+        // the rook threatens the undefended knight
+        let board = Board::from_fen("4k3/3r4/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.best_threat(1, None), Some(Move::Piece(D7, D4)));
+        assert_eq!(board.best_threat(2, None), Some(Move::Piece(D7, D4)));
+
+        let null = board.null_move().unwrap();
+        assert_eq!(null.get_turn_color(), BLACK);
+        assert_eq!(null.fen_placement(), board.fen_placement());
+
+        // no passing out of check
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        assert_eq!(board.null_move(), None);
+        assert_eq!(board.best_threat(1, None), None);
+    }
+
+    #[test]
+    fn test_center_control() {
+        let start = Board::default();
+        // only c3 to f3 for white and c6 to f6 for black
+        assert_eq!(start.center_control(WHITE), 4);
+        assert_eq!(start.center_control(BLACK), 4);
+
+        let e4 = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        assert!(e4.center_control(WHITE) > e4.center_control(BLACK));
+        assert_eq!(e4.center_control(BLACK), 4);
+
+        // the same position with the colors swapped and the board flipped
+        let e5 = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1").unwrap();
+        assert_eq!(e5.center_control(BLACK), e4.center_control(WHITE));
+        assert_eq!(e5.center_control(WHITE), e4.center_control(BLACK));
+
+        let full = Board::from_fen("4k3/8/8/2QQQQ2/2QQQQ2/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(full.center_control(WHITE), 20);
+    }
+
+    #[test]
+    fn test_result_after() {
+        let board = Board::from_fen("7k/8/6K1/8/8/8/8/5Q2 w - - 0 1").unwrap();
+        assert_eq!(board.result_after(Move::Piece(F1, F8)), GameResult::Victory(WHITE));
+        assert_eq!(board.result_after(Move::Piece(F1, F7)), GameResult::Stalemate);
+        assert_eq!(
+            board.result_after(Move::Piece(F1, F6)),
+            GameResult::Continuing(board.apply_eval_move(Move::Piece(F1, F6)))
+        );
+        assert_eq!(
+            board.result_after(Move::Piece(F1, H8)),
+            GameResult::IllegalMove(Move::Piece(F1, H8))
+        );
+        assert_eq!(board.result_after(Move::Resign), GameResult::Victory(BLACK));
+    }
+
+    #[test]
+    fn test_trapped_pieces() {
+        // the knight's only squares are taken by its own pawns
+        let cornered = Board::from_fen("4k3/8/8/8/8/1P6/2P5/N3K3 w - - 0 1").unwrap();
+        let central = Board::from_fen("4k3/8/8/8/3N4/1P6/2P5/4K3 w - - 0 1").unwrap();
+        assert_eq!(cornered.trapped_pieces_value_for(WHITE), -1.0);
+        assert_eq!(cornered.trapped_pieces_value_for(BLACK), 1.0);
+        assert_eq!(central.trapped_pieces_value_for(WHITE), 0.0);
+        assert!(cornered.trapped_pieces_value_for(WHITE) < central.trapped_pieces_value_for(WHITE));
+
+        // the classic bishop on a7, shut in by b6 and c7
+        let bishop = Board::from_fen("4k3/B1p5/1p6/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(bishop.trapped_pieces_value_for(WHITE), -2.0);
+
+        // engines weigh it like any other term
+        let engine = EvalEngine {
+            trapped_pieces: 1.5,
+            ..EvalEngine::default()
+        };
+        assert_eq!(bishop.static_eval(WHITE, engine), bishop.value_for(WHITE) - 3.0);
+        assert_eq!(bishop.static_eval(WHITE, None), bishop.value_for(WHITE));
+    }
+
+    #[test]
+    fn test_material_imbalance() {
+        // a bishop and a knight are both worth three pawns, but the pair of bishops is better
+        let bishops = Board::from_fen("2n1kn2/pppppppp/8/8/8/8/PPPPPPPP/2B1KB2 w - - 0 1").unwrap();
+        let knights = Board::from_fen("2n1kn2/pppppppp/8/8/8/8/PPPPPPPP/2N1KN2 w - - 0 1").unwrap();
+        assert_eq!(bishops.naive_value_for(WHITE), knights.naive_value_for(WHITE));
+        assert_eq!(bishops.material_imbalance_value_for(WHITE), 0.6);
+        assert_eq!(knights.material_imbalance_value_for(WHITE), 0.0);
+        assert!(bishops.material_imbalance_value_for(WHITE) > knights.material_imbalance_value_for(WHITE));
+        assert_eq!(bishops.material_imbalance_diff(), -bishops.material_imbalance_value_for(BLACK) * 2.0);
+
+        // a queen and two rooks get in each other's way
+        let heavy = Board::from_fen("4k3/8/8/8/8/8/8/R2QK2R w - - 0 1").unwrap();
+        assert_eq!(heavy.material_imbalance_value_for(WHITE), -0.4);
+
+        // engines that weigh it prefer the bishops
+        let engine = EvalEngine {
+            material: 0.0,
+            naive: 1.0,
+            material_imbalance: 1.0,
+            ..EvalEngine::default()
+        };
+        assert!(bishops.static_eval(WHITE, engine) > knights.static_eval(WHITE, engine));
+    }
+
+    #[test]
+    fn test_is_known_draw() {
+        // the light-squared bishop can never drive the king out of h8
+        let wrong_bishop = Board::from_fen("7k/8/8/8/7P/7P/4B3/4K3 w - - 0 1").unwrap();
+        assert!(wrong_bishop.is_known_draw());
+        let in_the_box = Board::from_fen("8/6k1/8/8/7P/8/4B3/4K3 b - - 0 1").unwrap();
+        assert!(in_the_box.is_known_draw());
+        let mirrored = Board::from_fen("4k3/3b4/8/p7/8/8/8/1K6 w - - 0 1").unwrap();
+        assert!(mirrored.is_known_draw());
+
+        // the right bishop, a far away king, a knight pawn or another pawn all win
+        let right_bishop = Board::from_fen("7k/8/8/8/7P/8/3B4/4K3 w - - 0 1").unwrap();
+        assert!(!right_bishop.is_known_draw());
+        let far_away = Board::from_fen("8/8/8/k7/7P/8/4B3/4K3 w - - 0 1").unwrap();
+        assert!(!far_away.is_known_draw());
+        let knight_pawn = Board::from_fen("6k1/8/8/8/6P1/8/4B3/4K3 w - - 0 1").unwrap();
+        assert!(!knight_pawn.is_known_draw());
+        let two_files = Board::from_fen("7k/8/8/8/7P/8/P3B3/4K3 w - - 0 1").unwrap();
+        assert!(!two_files.is_known_draw());
+
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/3NK3 w - - 0 1").unwrap().is_known_draw());
+        assert!(!Board::from_fen("4k3/8/8/8/8/8/8/3RK3 w - - 0 1").unwrap().is_known_draw());
+        assert!(!Board::default().is_known_draw());
+    }
+
+    #[test]
+    fn test_rook_behind_passer() {
+        // the same rook pushing the e-pawn from behind or stuck in front of it
+        let behind = Board::from_fen("k7/8/8/4P3/8/8/8/4R1K1 w - - 0 1").unwrap();
+        let in_front = Board::from_fen("k3R3/8/8/4P3/8/8/8/6K1 w - - 0 1").unwrap();
+        assert_eq!(behind.rook_behind_passer_value_for(WHITE), 0.5);
+        assert_eq!(in_front.rook_behind_passer_value_for(WHITE), 0.0);
+        assert!(behind.rook_behind_passer_value_for(WHITE) > in_front.rook_behind_passer_value_for(WHITE));
+        assert_eq!(behind.rook_behind_passer_diff(), 1.0);
+
+        // a rook behind the enemy's passed pawn restrains it
+        let restraining = Board::from_fen("k7/8/8/4P3/8/8/8/4r1K1 w - - 0 1").unwrap();
+        assert_eq!(restraining.rook_behind_passer_value_for(BLACK), 0.5);
+
+        // a piece in between, or a pawn that isn't passed, doesn't count
+        let blocked = Board::from_fen("k7/8/8/4P3/8/4N3/8/4R1K1 w - - 0 1").unwrap();
+        assert_eq!(blocked.rook_behind_passer_value_for(WHITE), 0.0);
+        let not_passed = Board::from_fen("k7/3p4/8/4P3/8/8/8/4R1K1 w - - 0 1").unwrap();
+        assert_eq!(not_passed.rook_behind_passer_value_for(WHITE), 0.0);
+
+        // engines that weigh it put the rook behind the pawn
+        let engine = EvalEngine {
+            rook_behind_passer: 1.0,
+            ..EvalEngine::default()
+        };
+        let gain = behind.static_eval(WHITE, engine) - behind.static_eval(WHITE, None);
+        assert_eq!(gain, 0.5);
+    }
+
+    #[test]
+    fn test_development() {
+        let start = Board::default();
+        assert_eq!(start.development_value_for(WHITE), -0.2);
+        assert_eq!(start.development_diff(), 0.0);
+
+        let mut board = start;
+        for m in &[
+            Move::Piece(E2, E4),
+            Move::Piece(E7, E5),
+            Move::Piece(G1, F3),
+            Move::Piece(B8, C6),
+        ] {
+            board = board.apply_eval_move(*m);
+        }
+        assert_eq!(board.development_value_for(WHITE), 0.1);
+        assert_eq!(board.development_value_for(BLACK), 0.1);
+
+        let castled = board
+            .apply_eval_move(Move::Piece(F1, C4))
+            .apply_eval_move(Move::Piece(G8, F6))
+            .apply_eval_move(Move::KingSideCastle);
+        assert!(castled.development_value_for(WHITE) > board.development_value_for(WHITE));
+        assert!(castled.development_diff() > 0.0);
+
+        // with only kings, pawns and developed knights left, it has faded away
+        let endgame = Board::from_fen("4k3/pppp4/2n5/8/8/2N5/4PPPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(endgame.development_value_for(WHITE), 0.0);
+        assert_eq!(endgame.development_value_for(BLACK), 0.0);
+
+        // engines that weigh it would rather develop than stay home
+        let engine = EvalEngine {
+            material: 0.0,
+            development: 1.0,
+            ..EvalEngine::default()
+        };
+        assert!(castled.static_eval(WHITE, engine) > board.static_eval(WHITE, engine));
+    }
+
+    #[test]
+    fn test_can_claim_fifty_move() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 0 1").unwrap();
+        let shuffle = [
+            Move::Piece(A1, A2),
+            Move::Piece(E8, D8),
+            Move::Piece(A2, A1),
+            Move::Piece(D8, E8),
+        ];
+        for ply in 0..100 {
+            assert!(!board.can_claim_fifty_move());
+            assert_eq!(board.moves_since_capture_or_pawn(), ply / 2);
+            board = board.apply_eval_move(shuffle[ply as usize % 4]);
+        }
+        assert!(board.can_claim_fifty_move());
+        assert_eq!(board.moves_since_capture_or_pawn(), 50);
+
+        let board = board.apply_eval_move(Move::Piece(E2, E4));
+        assert!(!board.can_claim_fifty_move());
+        assert_eq!(board.moves_since_capture_or_pawn(), 0);
+    }
+
+    #[test]
+    fn test_fifty_move_draw() {
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::Rook(WHITE, A1))
+            .piece(Piece::Pawn(WHITE, E2))
+            .piece(Piece::King(BLACK, E8))
+            .set_halfmove_clock(98)
+            .set_fullmove_number(80)
+            .build();
+        let board = match board.play_move(Move::Piece(A1, A2)) {
+            GameResult::Continuing(board) => board,
+            result => panic!("Ra2 gave {:?}", result),
+        };
+        assert_eq!(board.halfmove_clock(), 99);
+        assert!(board.fen().contains(" 99 "), "{}", board.fen());
+
+        // the 100th halfmove draws, unless it's a pawn move or a capture
+        assert_eq!(board.play_move(Move::Piece(E8, D8)), GameResult::Stalemate);
+        let board = board.set_turn(WHITE);
+        assert_eq!(board.play_move(Move::Piece(A2, A3)), GameResult::Stalemate);
+        match board.play_move(Move::Piece(E2, E4)) {
+            GameResult::Continuing(board) => assert_eq!(board.halfmove_clock(), 0),
+            result => panic!("e4 gave {:?}", result),
+        }
+
+        // checkmate on the 100th halfmove still wins
+        let board = BoardBuilder::from(back_rank_mate()).set_halfmove_clock(99).build();
+        assert_eq!(board.play_move(Move::Piece(A1, A8)), GameResult::Victory(WHITE));
+
+        // promoting resets the clock too
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 99 80").unwrap();
+        let promotion = Move::Promotion(B7, B8, Piece::Queen(WHITE, Position::new(-1, -1)));
+        match board.play_move(promotion) {
+            GameResult::Continuing(board) => assert_eq!(board.halfmove_clock(), 0),
+            result => panic!("b8=Q gave {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_move_causes_stalemate() {
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, B6))
+            .piece(Piece::Queen(WHITE, C1))
+            .piece(Piece::King(BLACK, A8))
+            .build();
+        let stalemates: Vec<Move> = board
+            .get_legal_moves()
+            .into_iter()
+            .filter(|m| board.move_causes_stalemate(*m))
+            .collect();
+        assert!(stalemates.contains(&Move::Piece(C1, C7)));
+        // mate isn't stalemate
+        assert!(!board.move_causes_stalemate(Move::Piece(C1, C8)));
+        assert!(!board.move_causes_stalemate(Move::Piece(C1, C2)));
+        for m in stalemates {
+            assert_eq!(board.play_move(m), GameResult::Stalemate);
+        }
+        assert!(!Board::default().move_causes_stalemate(Move::Piece(E2, E4)));
+    }
+
+    #[test]
+    fn test_check_moves() {
+        assert!(Board::default().check_moves().is_empty());
+
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, A1))
+            .piece(Piece::Rook(WHITE, E1))
+            .piece(Piece::Bishop(WHITE, E4))
+            .piece(Piece::Knight(WHITE, H5))
+            .piece(Piece::King(BLACK, E8))
+            .build();
+        let checks = board.check_moves();
+        // moving the bishop off the file uncovers the rook
+        assert!(checks.contains(&Move::Piece(E4, D5)));
+        assert!(checks.contains(&Move::Piece(E4, H7)));
+        // the knight checks by itself
+        assert!(checks.contains(&Move::Piece(H5, F6)));
+        assert!(!checks.contains(&Move::Piece(H5, G3)));
+        assert!(!checks.contains(&Move::Piece(A1, B1)));
+        for m in board.get_legal_moves() {
+            assert_eq!(checks.contains(&m), board.gives_check(m), "{}", m);
+        }
+    }
+
+    #[test]
+    fn test_score_relative() {
+        let board = Board::from_fen("r3k3/pp6/8/8/8/8/PPP5/RN2K3 w - - 0 1").unwrap();
+        for engine in [
+            [1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0, 0.0, 0.0],
+            [1.0, 2.0, 3.0, 0.0, 0.0, 0.0],
+        ] {
+            let white = board.score_relative(engine);
+            let black = board.set_turn(BLACK).score_relative(engine);
+            assert_eq!(white, board.static_eval(WHITE, Some(engine)));
+            assert!((white + black).abs() < 1e-9, "{} {}", white, black);
+        }
+        // white is a knight and a pawn up
+        assert!(board.score_relative([1.0, 0.0, 0.0, 0.0, 0.0, 0.0]) > 0.0);
+    }
+
+    #[test]
+    fn test_eval_components() {
+        let mut boards = vec![
+            Board::default(),
+            Board::from_fen("r3k3/pp6/8/8/8/8/PPP5/RN2K3 w - - 0 1").unwrap(),
+        ];
+        boards.extend(Board::default().play_random_game(&mut Rng::new(5), 20));
+        let engine = [1.0, 0.5, 2.0, -0.25, 0.75, 3.0];
+        for board in boards {
+            for color in [WHITE, BLACK] {
+                let components = board.eval_components(color);
+                let dot: f64 = components.iter().zip(engine.iter()).map(|(value, weight)| value * weight).sum();
+                let eval = board.static_eval(color, Some(engine));
+                assert!((dot - eval).abs() < 1e-9, "{} {}", dot, eval);
+            }
+        }
+    }
+
+    #[test]
+    fn test_heuristic_diffs() {
+        let mut boards = vec![
+            Board::default(),
+            Board::from_fen("r3k3/pp6/8/8/8/8/PPP5/RN2K3 w - - 0 1").unwrap(),
+            // black is checkmated
+            Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap(),
+        ];
+        boards.extend(Board::default().play_random_game(&mut Rng::new(8), 20));
+        for board in boards {
+            let diffs = [
+                (board.value_diff(), board.value_for(WHITE) - board.value_for(BLACK)),
+                (board.mobility_diff(), board.mobility_value_for(WHITE) - board.mobility_value_for(BLACK)),
+                (board.naive_diff(), board.naive_value_for(WHITE) - board.naive_value_for(BLACK)),
+                (board.control_diff(), board.control_value_for(WHITE) - board.control_value_for(BLACK)),
+                (board.closest_diff(), board.closest_value_for(WHITE) - board.closest_value_for(BLACK)),
+                (board.trade_diff(), board.trade_value_for(WHITE) - board.trade_value_for(BLACK)),
+                (
+                    board.trapped_pieces_diff(),
+                    board.trapped_pieces_value_for(WHITE) - board.trapped_pieces_value_for(BLACK),
+                ),
+            ];
+            for (i, (diff, expected)) in diffs.iter().enumerate() {
+                assert_eq!(diff, expected, "heuristic {} of {}", i, board.fen());
+            }
+        }
+    }
+
+    #[test]
+    fn test_has_mating_material() {
+        let white = |fen: &str| Board::from_fen(fen).unwrap().has_mating_material(WHITE);
+        assert!(!white("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(!white("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(!white("4k3/8/8/8/8/8/8/4KB2 w - - 0 1"));
+        // both bishops on light squares
+        assert!(!white("4k3/8/8/8/8/8/8/3BKB2 w - - 0 1"));
+
+        assert!(white("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+        assert!(white("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+        assert!(white("4k3/8/8/8/8/8/8/4KNN1 w - - 0 1"));
+        assert!(white("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"));
+        assert!(white("4k3/8/8/8/8/8/8/4KBN1 w - - 0 1"));
+
+        // only the side asked about counts
+        let board = Board::from_fen("q3k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(!board.has_mating_material(WHITE));
+        assert!(board.has_mating_material(BLACK));
+    }
+
+    #[test]
+    fn test_revert() {
+        let cases = [
+            // capture
+            ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", Move::Piece(E4, D5)),
+            // castle
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 4 20", Move::KingSideCastle),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 4 20", Move::QueenSideCastle),
+            // en passant
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 30", Move::Piece(E5, D6)),
+            // promotion, with a capture
+            ("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 40", Move::Promotion(A7, B8, Piece::Rook(WHITE, B8))),
+        ];
+        for (fen, m) in cases.iter() {
+            let board = Board::from_fen(fen).unwrap();
+            let (mut next, undo) = board.with_move_applied(*m);
+            assert_eq!(next, board.apply_eval_move(*m));
+            assert_ne!(next.fen(), board.fen());
+            next.revert(undo);
+            assert_eq!(next.fen(), board.fen());
+            assert_eq!(next, board);
+        }
+    }
+
+    #[test]
+    fn test_swap_colors() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 3 9").unwrap();
+        let swapped = board.swap_colors();
+        assert_eq!(swapped.fen_placement(), "R3K2R/P1PPQPB1/BN2PNP1/3pn3/1P2p3/2n2q1P/pppbbppp/r3k2r");
+        assert!(swapped.get_castling_rights(BLACK).can_kingside_castle());
+        assert!(!swapped.get_castling_rights(BLACK).can_queenside_castle());
+        assert!(swapped.get_castling_rights(WHITE).can_queenside_castle());
+        assert_eq!(swapped.get_piece(E1), Some(Piece::King(BLACK, E1)));
+        assert_eq!(swapped.get_piece(E8), Some(Piece::King(WHITE, E8)));
+        assert_eq!(swapped.get_turn_color(), BLACK);
+        assert_eq!(swapped.naive_value_for(BLACK), board.naive_value_for(WHITE));
+
+        assert_eq!(swapped.swap_colors(), board);
+        assert_eq!(swapped.swap_colors().fen(), board.fen());
+        assert_eq!(Board::default().swap_colors().swap_colors(), Board::default());
+    }
+
+    #[test]
+    fn test_make_unmake_move() {
+        let mut rng = Rng::new(5);
+        let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let original = board;
+        let mut undos = vec![];
+        let mut fens = vec![];
+        for _ in 0..20 {
+            let moves = board.get_legal_moves();
+            let m = moves[rng.below(moves.len())];
+            fens.push(board.fen());
+            let expected = board.apply_eval_move(m);
+            undos.push(board.make_move(m));
+            assert_eq!(board, expected);
+        }
+
+        while let Some(undo) = undos.pop() {
+            board.revert(undo);
+            assert_eq!(board.fen(), fens.pop().unwrap());
+        }
+        assert_eq!(board.fen(), original.fen());
+        assert_eq!(board, original);
+    }
+
+    #[test]
+    fn test_exchange_sequence() {
+        assert!(Board::default().exchange_sequence(E4).is_empty());
+        assert!(Board::default().exchange_sequence(E2).is_empty());
+
+        // knight takes, rook retakes, bishop takes again, queen retakes
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, H1))
+            .piece(Piece::Knight(WHITE, C3))
+            .piece(Piece::Bishop(WHITE, A2))
+            .piece(Piece::King(BLACK, H8))
+            .piece(Piece::Pawn(BLACK, D5))
+            .piece(Piece::Rook(BLACK, D8))
+            .piece(Piece::Queen(BLACK, A8))
+            .build();
+        let sequence = board.exchange_sequence(D5);
+        assert_eq!(
+            sequence,
+            vec![
+                (C3, Piece::Knight(WHITE, C3)),
+                (D8, Piece::Rook(BLACK, D8)),
+                (A2, Piece::Bishop(WHITE, A2)),
+                (A8, Piece::Queen(BLACK, A8)),
+            ]
+        );
+        let values = PieceValues::default();
+        for pair in sequence.windows(2) {
+            assert_ne!(pair[0].1.get_color(), pair[1].1.get_color());
+        }
+        for pair in sequence.windows(3) {
+            assert!(values.value_cp(pair[0].1) <= values.value_cp(pair[2].1));
+        }
+
+        // with pawns in front, white's knight would only win a pawn for a
+        // knight, so the exchange stops after the pawns
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, H1))
+            .piece(Piece::Pawn(WHITE, E4))
+            .piece(Piece::Knight(WHITE, C3))
+            .piece(Piece::Rook(WHITE, D1))
+            .piece(Piece::King(BLACK, H8))
+            .piece(Piece::Pawn(BLACK, D5))
+            .piece(Piece::Pawn(BLACK, E6))
+            .piece(Piece::Knight(BLACK, F6))
+            .piece(Piece::Rook(BLACK, D8))
+            .build();
+        assert_eq!(
+            board.exchange_sequence(D5),
+            vec![(E4, Piece::Pawn(WHITE, E4)), (E6, Piece::Pawn(BLACK, E6))]
+        );
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let shuffle = [
+            Move::Piece(B1, C3),
+            Move::Piece(B8, C6),
+            Move::Piece(C3, B1),
+            Move::Piece(C6, B8),
+        ];
+        let mut board = Board::default();
+        let mut history = PositionHistory::default();
+        assert_eq!(history.repetitions(&board), 1);
+        for (i, m) in shuffle.iter().chain(shuffle[..3].iter()).enumerate() {
+            board = match board.play_move_with(*m, &mut history) {
+                GameResult::Continuing(board) => board,
+                result => panic!("{} gave {:?}", m, result),
+            };
+            assert_eq!(history.repetitions(&board), if i < 3 { 1 } else { 2 });
+        }
+
+        // the starting position comes up for the third time
+        let mut repeated = history.clone();
+        assert_eq!(board.play_move_with(Move::Piece(C6, B8), &mut repeated), GameResult::Stalemate);
+        assert!(!board.apply_eval_move(Move::Piece(C6, B8)).is_stalemate());
+
+        // a pawn move means nothing before it can repeat
+        let board = match board.play_move_with(Move::Piece(A7, A6), &mut history) {
+            GameResult::Continuing(board) => board,
+            result => panic!("a6 gave {:?}", result),
+        };
+        assert_eq!(history.repetitions(&board), 1);
+        assert_eq!(history, PositionHistory::default());
+    }
+
+    #[test]
+    fn test_value_of() {
+        let board = Board::default();
+        let values = PieceValues::default();
+        for square in &board.squares {
+            if let Some(piece) = square.get_piece() {
+                assert_eq!(board.value_of(piece), values.value_cp(piece));
+                assert_eq!(board.value_of(piece), piece.get_material_value() * 100);
+            }
+        }
+
+        // the pawn can take a knight or a bishop, and takes whichever the
+        // piece values say is worth more
+        let board = Board::from_fen("4k3/8/8/2n1b3/3P4/8/8/4K3 w - - 0 1").unwrap();
+        let knight = PieceValues {
+            knight: 3.5,
+            ..PieceValues::default()
+        };
+        let bishop = PieceValues {
+            bishop: 3.5,
+            ..PieceValues::default()
+        };
+        assert_eq!(board.best_capture_with(&knight), Some((Move::Piece(D4, C5), 350)));
+        assert_eq!(board.best_capture_with(&bishop), Some((Move::Piece(D4, E5), 350)));
+        let (_, value) = board.best_capture().unwrap();
+        assert_eq!(value, board.value_of(Piece::Knight(BLACK, C5)));
+        assert_eq!(board.get_material_advantage(WHITE) as f64, board.naive_value_for(WHITE));
+
+        // the board's own piece values reach every consumer, and the
+        // positions played from it
+        for (values, target, other) in [(knight, C5, E5), (bishop, E5, C5)] {
+            let board = BoardBuilder::from(board).set_piece_values(values).build();
+            let prey = board.get_piece(target).unwrap();
+            assert_eq!(board.value_of(prey), 350);
+            assert_eq!(board.see(Move::Piece(D4, target)), 350);
+            assert_eq!(board.see(Move::Piece(D4, other)), 300);
+            assert_eq!(board.best_capture(), Some((Move::Piece(D4, target), 350)));
+            assert_eq!(board.order_moves(board.get_capture_moves())[0], Move::Piece(D4, target));
+            assert_eq!(board.naive_value_for(WHITE), 1.0 - 3.0 - 3.5);
+            assert_eq!(board.get_material_advantage(BLACK), 6);
+            assert_eq!(board.apply_eval_move(Move::Piece(E1, E2)).value_of(prey), 350);
+        }
+    }
+
+    #[test]
+    fn test_annotate_move() {
+        let board = Board::default();
+        assert_eq!(
+            board.annotate_move(Move::Piece(G1, F3)),
+            MoveNotation {
+                uci: String::from("g1f3"),
+                san: String::from("Nf3"),
+            }
+        );
+
+        let positions = [
+            "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/1p6/R3K2R b KQkq - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 30",
+        ];
+        let mut boards: Vec<Board> = positions.iter().map(|fen| Board::from_fen(fen).unwrap()).collect();
+        boards.extend(Board::default().play_random_game(&mut Rng::new(21), 20));
+        for board in boards {
+            for m in board.get_legal_moves() {
+                let notation = board.annotate_move(m);
+                let from_uci = board.move_from_uci(&notation.uci).unwrap();
+                let from_san = board.move_from_san(&notation.san).unwrap();
+                // a pawn reaching the last rank reads back as a promotion
+                // to a queen, which leaves the same pieces on the board
+                for read in [from_uci, from_san] {
+                    assert_eq!(
+                        board.apply_eval_move(read).fen_placement(),
+                        board.apply_eval_move(m).fen_placement(),
+                        "{}",
+                        notation.san
+                    );
+                }
+                if !notation.uci.ends_with('q') {
+                    assert_eq!(from_uci, m);
+                    assert_eq!(from_san, m);
+                }
+            }
         }
+        assert!(board.move_from_uci("e2e4x").is_err());
+        assert!(board.move_from_san("e5").is_err());
     }
 }
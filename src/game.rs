@@ -1,8 +1,9 @@
 use alloc::string::String;
+use alloc::vec::Vec;
 
-use crate::board::Board;
+use crate::board::{Board, PositionHistory};
 use crate::util::{format_fen, parse_fen, parse_san_move};
-use crate::{Color, GameResult};
+use crate::{Color, Evaluate, GameResult, Move};
 
 pub enum GameAction {
     // accept draw if previous action was OfferDraw
@@ -42,6 +43,10 @@ pub enum GameOver {
     BlackResigns,
     Stalemate,
     DrawAccepted,
+    // same position reached three times
+    Repetition,
+    // fifty moves by each side without a capture or pawn move
+    FiftyMoves,
 }
 
 // wrapper around chess_engine::Board
@@ -53,6 +58,13 @@ pub struct Game {
     pub board: Board,
     pub draw_offered: Option<Color>,
     pub status: Option<GameOver>,
+    // legal moves of the board they were generated for, kept until a move
+    // is made so callers can ask for them repeatedly without regenerating
+    legal_moves: Option<(Board, Vec<Move>)>,
+    // the positions since the last capture or pawn move, for threefold repetition
+    history: PositionHistory,
+    // every move played so far, including a resignation
+    moves: Vec<Move>,
 }
 
 impl Game {
@@ -71,11 +83,15 @@ impl Game {
             board,
             draw_offered,
             status,
+            legal_moves: None,
+            history: PositionHistory::default(),
+            moves: Vec::new(),
         })
     }
 
-    pub fn to_fen(&self, halfmove_clock: u8, fullmove_number: u8) -> Result<String, String> {
-        format_fen(&self.board, halfmove_clock, fullmove_number)
+    // the board keeps its own move counters, like Board::fen
+    pub fn to_fen(&self) -> String {
+        format_fen(&self.board)
     }
 
     // convenience accessor for board.get_turn_color
@@ -83,6 +99,98 @@ impl Game {
         self.board.get_turn_color()
     }
 
+    // moves played so far, in order
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    // every move played so far, two bytes per move
+    //
+    // see Move::to_u16 for the encoding, each move is stored big endian
+    pub fn serialize_moves(&self) -> Vec<u8> {
+        self.moves
+            .iter()
+            .flat_map(|m| m.to_u16().to_be_bytes())
+            .collect()
+    }
+
+    // replay moves stored by serialize_moves, starting from start
+    pub fn deserialize_moves(bytes: &[u8], start: Board) -> Result<Game, String> {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(String::from("move data has an odd number of bytes"));
+        }
+        let mut game = Game {
+            board: start,
+            ..Game::default()
+        };
+        for (i, pair) in bytes.chunks(2).enumerate() {
+            let m = Move::from_u16(u16::from_be_bytes([pair[0], pair[1]]))?;
+            if let GameResult::IllegalMove(m) = game.play(m) {
+                return Err(format!("move {} ({}) is illegal", i + 1, m));
+            }
+        }
+        Ok(game)
+    }
+
+    // legal moves for current turn
+    //
+    // generated once and reused until the board changes
+    pub fn legal_moves(&mut self) -> &[Move] {
+        let board = self.board;
+        match &mut self.legal_moves {
+            Some((cached_board, _)) if *cached_board == board => {}
+            cache => *cache = Some((board, board.get_legal_moves())),
+        }
+        match &self.legal_moves {
+            Some((_, moves)) => moves,
+            None => &[],
+        }
+    }
+
+    // play a move for current turn
+    //
+    // like Board::play_move_with, this returns GameResult::Stalemate for a
+    // threefold repetition or once fifty moves pass without a capture or pawn
+    // move, and the game's status says which it was
+    pub fn play(&mut self, m: Move) -> GameResult {
+        if self.status.is_some() {
+            return GameResult::IllegalMove(m);
+        }
+        let color = self.get_turn_color();
+        let result = self.board.play_move_with(m, &mut self.history);
+        if !matches!(result, GameResult::IllegalMove(_)) {
+            self.moves.push(m);
+        }
+        self.status = match result {
+            GameResult::Continuing(board) => {
+                self.board = board;
+                self.legal_moves = None;
+                None
+            }
+            GameResult::IllegalMove(_) => None,
+            GameResult::Stalemate => {
+                let board = self.board.apply_eval_move(m);
+                if board.is_stalemate() {
+                    Some(GameOver::Stalemate)
+                } else if self.history.is_threefold_repetition(&board) {
+                    Some(GameOver::Repetition)
+                } else {
+                    Some(GameOver::FiftyMoves)
+                }
+            }
+            GameResult::Victory(_) if m == Move::Resign => match color {
+                Color::Black => Some(GameOver::BlackResigns),
+                Color::White => Some(GameOver::WhiteResigns),
+            },
+            GameResult::Victory(color) => match color {
+                Color::Black => Some(GameOver::BlackCheckmates),
+                Color::White => Some(GameOver::WhiteCheckmates),
+            },
+        };
+
+        result
+    }
+
     // make a move for current turn
     pub fn make_move(&mut self, action: &GameAction) -> Result<&Option<GameOver>, GameError> {
         if self.status.is_some() {
@@ -120,29 +228,20 @@ impl Game {
             }
         };
 
+        let color = self.get_turn_color();
+        if let GameResult::IllegalMove(_) = self.play(chess_move) {
+            return Err(GameError::InvalidMove {});
+        }
         self.draw_offered = match draw_offered {
-            true => Some(self.get_turn_color()),
+            true => Some(color),
             false => None,
         };
-        self.status = match self.board.play_move(chess_move) {
-            GameResult::Continuing(board) => {
-                self.board = board;
-                None
-            }
-            GameResult::IllegalMove(_) => {
-                return Err(GameError::InvalidMove {});
-            }
-            GameResult::Stalemate => Some(GameOver::Stalemate),
-            GameResult::Victory(color) => match color {
-                Color::Black => Some(GameOver::BlackCheckmates),
-                Color::White => Some(GameOver::WhiteCheckmates),
-            },
-        };
         Ok(&self.status)
     }
 
     // resign
     fn resign(&mut self) -> Result<&Option<GameOver>, GameError> {
+        self.moves.push(Move::Resign);
         self.status = match self.get_turn_color() {
             Color::Black => Some(GameOver::BlackResigns),
             Color::White => Some(GameOver::WhiteResigns),
@@ -155,7 +254,7 @@ impl Game {
 mod tests {
     use super::*;
     use crate::piece::Piece;
-    use crate::position::Position;
+    use crate::position::*;
 
     #[test]
     fn test_game_moves() {
@@ -197,4 +296,132 @@ mod tests {
             Some(Piece::Rook(Color::White, Position::pgn("c8").unwrap()))
         )
     }
+
+    #[test]
+    fn test_accepted_draw_offer() {
+        let mut game = Game::default();
+        game.make_move(&GameAction::OfferDraw(String::from("e4")))
+            .expect("e4");
+        assert_eq!(game.draw_offered, Some(Color::White));
+        assert_eq!(
+            game.make_move(&GameAction::AcceptDraw),
+            Ok(&Some(GameOver::DrawAccepted))
+        );
+        assert_eq!(
+            game.make_move(&GameAction::from("e5")),
+            Err(GameError::GameAlreadyOver)
+        );
+    }
+
+    #[test]
+    fn test_declined_draw_offer() {
+        let mut game = Game::default();
+        // the player offering a draw can't accept it themselves
+        game.make_move(&GameAction::OfferDraw(String::from("e4")))
+            .expect("e4");
+        game.make_move(&GameAction::from("e5")).expect("e5");
+        assert_eq!(game.draw_offered, None);
+        assert_eq!(
+            game.make_move(&GameAction::AcceptDraw),
+            Err(GameError::InvalidMove)
+        );
+        assert_eq!(game.status, None);
+
+        game.make_move(&GameAction::OfferDraw(String::from("Nf3")))
+            .expect("Nf3");
+        game.make_move(&GameAction::from("Nc6")).expect("Nc6");
+        assert_eq!(
+            game.make_move(&GameAction::AcceptDraw),
+            Err(GameError::InvalidMove)
+        );
+        assert_eq!(game.status, None);
+    }
+
+    #[test]
+    fn test_legal_move_cache() {
+        let mut game = Game::default();
+        let moves = game.legal_moves().to_vec();
+        assert_eq!(moves.len(), 20);
+        assert_eq!(game.legal_moves(), &moves[..]);
+        assert_eq!(game.legal_moves, Some((game.board, moves.clone())));
+
+        game.make_move(&GameAction::from("e4")).expect("e4");
+        assert_eq!(game.legal_moves, None);
+        let replies = game.board.get_legal_moves();
+        assert_eq!(game.legal_moves(), &replies[..]);
+
+        // replacing the board directly also invalidates the cache
+        game.board = Board::default();
+        assert_eq!(game.legal_moves(), &moves[..]);
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let mut game = Game::default();
+        let mut board = Board::default();
+        let mut history = PositionHistory::default();
+        let shuffle = [
+            Move::Piece(G1, F3),
+            Move::Piece(G8, F6),
+            Move::Piece(F3, G1),
+            Move::Piece(F6, G8),
+        ];
+        for m in shuffle.iter().chain(shuffle[..3].iter()) {
+            assert!(matches!(game.play(*m), GameResult::Continuing(_)));
+            board = match board.play_move_with(*m, &mut history) {
+                GameResult::Continuing(board) => board,
+                _ => panic!("{} failed", m),
+            };
+        }
+
+        // the starting position comes up for the third time
+        assert_eq!(game.play(Move::Piece(F6, G8)), GameResult::Stalemate);
+        assert_eq!(game.status, Some(GameOver::Repetition));
+        assert_eq!(board.play_move_with(Move::Piece(F6, G8), &mut history), GameResult::Stalemate);
+        // a board on its own doesn't know it has been here before
+        assert!(matches!(board.play_move(Move::Piece(F6, G8)), GameResult::Continuing(_)));
+        assert_eq!(game.play(Move::Piece(G1, F3)), GameResult::IllegalMove(Move::Piece(G1, F3)));
+    }
+
+    #[test]
+    fn test_fifty_move_rule() {
+        let fen = "4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80";
+        let mut game = Game::from_fen(fen, None, None).unwrap();
+        assert_eq!(game.play(Move::Piece(A1, A7)), GameResult::Stalemate);
+        assert_eq!(game.status, Some(GameOver::FiftyMoves));
+        assert_eq!(
+            Board::from_fen(fen).unwrap().play_move(Move::Piece(A1, A7)),
+            GameResult::Stalemate
+        );
+
+        // a pawn move resets the clock
+        let mut game = Game::from_fen(fen, None, None).unwrap();
+        assert!(matches!(game.play(Move::Piece(E2, E4)), GameResult::Continuing(_)));
+        assert_eq!(game.status, None);
+    }
+
+    #[test]
+    fn test_serialize_moves() {
+        let mut game = Game::default();
+        let game_moves = vec![
+            "e4", "d5", "exd5", "Nf6", "Bb5", "c6", "dxc6", "Qb6", "cxb7", "Qxb5", "bxc8R",
+            "Kd7", "Nf3", "e6", "d3", "Bd6", "0-0",
+        ];
+        for game_move in game_moves {
+            game.make_move(&GameAction::from(game_move))
+                .expect(game_move);
+        }
+        game.make_move(&GameAction::Resign).unwrap();
+
+        let bytes = game.serialize_moves();
+        assert_eq!(bytes.len(), 2 * 18);
+        let replayed = Game::deserialize_moves(&bytes, Board::default()).unwrap();
+        assert_eq!(replayed.moves(), game.moves());
+        assert_eq!(replayed.board, game.board);
+        assert_eq!(replayed.status, Some(GameOver::BlackResigns));
+
+        assert!(Game::deserialize_moves(&bytes[..3], Board::default()).is_err());
+        // the same moves with black to move first
+        assert!(Game::deserialize_moves(&bytes, Board::default().set_turn(Color::Black)).is_err());
+    }
 }
@@ -11,7 +11,10 @@ use core::{convert::TryFrom, fmt::Debug, cmp::Ordering};
 extern crate std;
 
 mod board;
-pub use board::{Board, BoardBuilder};
+pub use board::{
+    Board, BoardBuilder, CastleLegality, CastleSide, IllegalMoveReason, MoveInput, MoveNotation, MoveUndo,
+    PositionHistory, EMPTY_SAFETY,
+};
 
 mod game;
 pub use game::{Game, GameAction, GameError, GameOver};
@@ -20,7 +23,7 @@ mod square;
 pub use square::{Square, EMPTY_SQUARE};
 
 mod piece;
-pub use piece::Piece;
+pub use piece::{Piece, PieceValues};
 
 mod position;
 pub use position::*;
@@ -28,14 +31,50 @@ pub use position::*;
 mod util;
 pub use util::*;
 
+mod search;
+pub use search::{
+    next_depth_fits, time_to_next_depth, Bound, EvalEngine, EvalSplit, QuiescenceConfig, Score, SearchResult, TtEntry,
+};
+use search::{score_from_tt, score_to_tt};
+
+mod zobrist;
+
+mod rating;
+pub use rating::{draw_score_for_white, expected_score, update_elo, update_elo_with_score, DEFAULT_K_FACTOR};
+
+mod pgn;
+pub use pgn::{from_pgn_moves, PgnGame};
+
+mod tournament;
+pub use tournament::{
+    compare_engines, play_game, play_game_with, BlunderPolicy, GameRecord, MatchConfig, MatchReport,
+    Outcome, Sprt, SprtVerdict,
+};
+
 use rayon::prelude::*;
-use std::{sync::{Arc, Mutex}, println};
+use std::{sync::{Arc, Mutex}, println, time::{Duration, Instant}};
 use dashmap::DashMap;
 use rand::seq::SliceRandom;
 
 pub const WHITE: Color = Color::White;
 pub const BLACK: Color = Color::Black;
 
+/// The number of centipawns in one point of static evaluation.
+/// A pawn is worth ten points in the piece tables.
+pub const CENTIPAWNS_PER_POINT: f64 = 10.0;
+
+/// The centipawn score of delivering checkmate on the spot. Mates further
+/// away score one centipawn less for every ply it takes to deliver them.
+pub const MATE_SCORE_CP: i32 = 1_000_000;
+
+/// The value `minimax` gives delivering checkmate, plus one for every ply
+/// of search depth left over, so that quicker mates are preferred.
+pub const MATE_VALUE: f64 = 100_000.0;
+
+/// The game phase of a position with all of its starting pieces.
+/// See [`Board::phase`].
+pub const MAX_PHASE: i32 = 24;
+
 /// The result of a move being played on the board.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum GameResult {
@@ -55,9 +94,13 @@ pub enum GameResult {
     /// 4. The player only has a king and a bishop
     /// 5. The player only has a king and two bishops
     ///
-    /// In a regular game of chess, threefold repetition also triggers
-    /// a stalemate, but this engine does not have builtin support for
-    /// threefold repetition detection yet.
+    /// Bishops only count as insufficient while they all stand on squares
+    /// of one color, so a king and bishop each on opposite colors play on.
+    ///
+    /// Reaching the same position for the third time, with the same
+    /// player to move, the same castling rights and the same usable en
+    /// passant square, is also a stalemate by threefold repetition when
+    /// the game keeps a `PositionHistory`. See `Board::play_move_with`.
     Stalemate,
     /// An illegal move was made. This can include many things,
     /// such as moving a piece through another piece, attempting
@@ -120,9 +163,13 @@ pub enum Move {
     /// Castling can only be performed if
     /// 1. The king has not moved at all since the game began
     /// 2. The respective rook (kingside or queenside) has also not moved
-    /// 3. The square adjacent to the king on the respective side is not threatened by an enemy piece
+    /// 3. There are no pieces between the king and the rook
+    /// 4. The king is not currently in check
+    /// 5. Neither the square the king passes over nor the square it lands on
+    ///    is threatened by an enemy piece
     ///
-    /// If all of these conditions are satisfied, castling is a legal move
+    /// If all of these conditions are satisfied, castling is a legal move.
+    /// `Board::castle_legality` reports which condition a castle fails.
     QueenSideCastle,
     /// If the current player is white, move the king to the G1 square, and the kingside rook to
     /// the F1 square. If the current player is black, however, move the king to the G8 square,
@@ -220,10 +267,121 @@ impl Move {
     /// - `"e2 e4"`
     /// - `"e2 to e4"`
     ///
-    /// Parsing a move such as `"knight to e4"` or `"Qxe4"` will NOT work.
+    /// Parsing a move such as `"knight to e4"` or `"Qxe4"` will NOT work,
+    /// since reading standard algebraic notation needs a board. Use
+    /// `Move::from_san` for that.
     pub fn parse(repr: String) -> Result<Self, String> {
         Self::try_from(repr)
     }
+
+    /// Write the move in standard algebraic notation, like `"exd5"`,
+    /// `"Rad1"`, `"O-O"`, `"e8=Q"` or `"Qh7#"`, as played on `board`.
+    ///
+    /// This is the same as `Board::move_to_san`, and `Move::from_san` reads
+    /// it back.
+    pub fn to_san(&self, board: &Board) -> String {
+        board.move_to_san(*self)
+    }
+
+    /// Write the move in the long algebraic notation UCI uses, like
+    /// `"e2e4"`, `"e7e8q"`, or `"e1g1"` for castling kingside as White.
+    ///
+    /// Castling moves don't say which side castles, so this needs the
+    /// board the move is played on. This is the same as
+    /// `Board::move_to_uci`.
+    pub fn to_uci(&self, board: &Board) -> String {
+        board.move_to_uci(*self)
+    }
+
+    /// Parse a move in the four or five character long algebraic notation
+    /// UCI uses, played on `board`.
+    ///
+    /// A king moving two squares sideways from its starting square is read
+    /// as castling. This is the same as `Board::move_from_uci`.
+    pub fn from_uci(uci: &str, board: &Board) -> Result<Self, String> {
+        board.move_from_uci(uci)
+    }
+
+    /// Parse a move in standard algebraic notation, like `"Nf3"`, `"exd5"`,
+    /// `"Qxe4+"`, `"e8=Q"` or `"Rad1"`, played on `board`.
+    ///
+    /// Check and mate marks are optional, as are annotations like `!` or
+    /// `?`. Castling may be written with letters or zeros, and the `=`
+    /// before a promotion may be left out. A pawn moving to the last rank
+    /// without a promotion becomes a queen. This returns an error saying
+    /// why when the move can't be read, isn't legal, or could be made by
+    /// more than one piece.
+    pub fn from_san(san: &str, board: &Board) -> Result<Self, String> {
+        let trimmed = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let m = match trimmed {
+            "O-O" | "0-0" | "o-o" => Move::KingSideCastle,
+            "O-O-O" | "0-0-0" | "o-o-o" => Move::QueenSideCastle,
+            other => match parse_san_move(board, &other.replace('=', "")) {
+                Ok(m) => m,
+                Err(e) if e == "ambiguous move" => {
+                    return Err(format!("`{}` could be made by more than one piece", san))
+                }
+                Err(e) if e == "no matching move" => return Err(format!("`{}` is not a legal move", san)),
+                Err(_) => return Err(format!("`{}` is not a move in standard algebraic notation", san)),
+            },
+        };
+
+        if !board.is_legal_move(m, board.get_turn_color()) {
+            return Err(format!("`{}` is not a legal move", san));
+        }
+        if trimmed.contains('x') && !board.is_capture(m) {
+            return Err(format!("`{}` doesn't capture anything", san));
+        }
+        Ok(m)
+    }
+
+    /// Pack the move into 16 bits, for storing games compactly.
+    ///
+    /// The low six bits hold the square the move starts from and the next
+    /// six the square it ends on, each numbered `row * 8 + col`. The top
+    /// four bits hold the kind of move: 0 for a plain move, 1 to 4 for a
+    /// promotion to a knight, bishop, rook or queen, 5 and 6 for kingside
+    /// and queenside castling, and 7 for resigning.
+    pub fn to_u16(&self) -> u16 {
+        let square = |pos: &Position| (pos.get_row() * 8 + pos.get_col()) as u16;
+        match self {
+            Move::Piece(from, to) => square(from) | square(to) << 6,
+            Move::Promotion(from, to, piece) => {
+                let kind = match piece {
+                    Piece::Knight(..) => 1,
+                    Piece::Bishop(..) => 2,
+                    Piece::Rook(..) => 3,
+                    _ => 4,
+                };
+                square(from) | square(to) << 6 | kind << 12
+            }
+            Move::KingSideCastle => 5 << 12,
+            Move::QueenSideCastle => 6 << 12,
+            Move::Resign => 7 << 12,
+        }
+    }
+
+    /// Unpack a move packed with `to_u16`.
+    ///
+    /// A promotion piece comes back with the color of the side that
+    /// promotes and an off-board position, as `parse_san_move` makes it.
+    pub fn from_u16(bits: u16) -> Result<Self, String> {
+        let from = Position::new((bits & 63) as i32 / 8, (bits & 63) as i32 % 8);
+        let to = Position::new((bits >> 6 & 63) as i32 / 8, (bits >> 6 & 63) as i32 % 8);
+        let color = if to.get_row() == 7 { WHITE } else { BLACK };
+        let offboard = Position::new(-1, -1);
+        Ok(match bits >> 12 {
+            0 => Move::Piece(from, to),
+            1 => Move::Promotion(from, to, Piece::Knight(color, offboard)),
+            2 => Move::Promotion(from, to, Piece::Bishop(color, offboard)),
+            3 => Move::Promotion(from, to, Piece::Rook(color, offboard)),
+            4 => Move::Promotion(from, to, Piece::Queen(color, offboard)),
+            5 => Move::KingSideCastle,
+            6 => Move::QueenSideCastle,
+            7 => Move::Resign,
+            kind => return Err(format!("invalid move kind `{}`", kind)),
+        })
+    }
 }
 
 impl core::fmt::Display for Move {
@@ -249,8 +407,18 @@ pub trait Evaluate: Sized where Self: Sync {
     /// Get the value of the board based on the mobility of pieces
     fn mobility_value_for(&self, color: Color) -> f64;
 
-    /// Get the value of the board based on the simple amount of pieces
-    fn naive_value_for(&self, color: Color) -> f64;
+    /// Get the piece values this position counts material with.
+    fn piece_values(&self) -> PieceValues;
+
+    /// Get the value of the board based on the simple amount of material,
+    /// counted with the position's own piece values
+    fn naive_value_for(&self, color: Color) -> f64 {
+        self.naive_value_with(color, &self.piece_values())
+    }
+
+    /// Get the value of the board based on the simple amount of material,
+    /// counted with the given piece values
+    fn naive_value_with(&self, color: Color, values: &PieceValues) -> f64;
 
     /// Get the value of the board based on how many squares each side controls
     fn control_value_for(&self, color: Color) -> f64;
@@ -261,6 +429,29 @@ pub trait Evaluate: Sized where Self: Sync {
     /// Get the value of the board, minimizing the amount of material on the board
     fn trade_value_for(&self, color: Color) -> f64;
 
+    /// Get the value of the board based on how many knights and bishops
+    /// each side has trapped with almost no moves. Trapped pieces deep in
+    /// enemy territory, like a bishop on a7, count double.
+    fn trapped_pieces_value_for(&self, color: Color) -> f64;
+
+    /// Get the value of the board based on which combinations of pieces
+    /// each side has, beyond what the pieces are worth one by one: the
+    /// bishop pair is worth more than two minor pieces, while a second
+    /// knight or rook, or a rook alongside a queen, is worth a little less.
+    fn material_imbalance_value_for(&self, color: Color) -> f64;
+
+    /// Get the value of the board based on rooks standing behind passed
+    /// pawns on their file, where they push their own pawns forward and
+    /// hold the enemy's back.
+    fn rook_behind_passer_value_for(&self, color: Color) -> f64;
+
+    /// Get how well a player has developed for the opening: knights and
+    /// bishops off their starting squares count for them, and a king still
+    /// in the middle of the back rank counts against them. Unlike the other
+    /// heuristics, this only rates `color`'s own pieces, and it fades out
+    /// as pieces come off the board.
+    fn development_value_for(&self, color: Color) -> f64;
+
     /// Get the current player's color.
     fn get_current_player_color(&self) -> Color;
 
@@ -279,6 +470,486 @@ pub trait Evaluate: Sized where Self: Sync {
     //Create a concise string representation of the board for caching
     fn cache_repr(&self) -> String;
 
+    /// Get a 64-bit hash of the position for caching, covering the pieces,
+    /// the player to move, castling rights and any usable en passant square.
+    fn zobrist_hash(&self) -> u64;
+
+    /// Get the legal captures for the current player.
+    fn get_capture_moves(&self) -> Vec<Move>;
+
+    /// Get the legal moves for the current player that change the material
+    /// on the board: captures and pawns reaching the last rank.
+    fn get_noisy_moves(&self) -> Vec<Move>;
+
+    /// Sort moves so the ones most likely to be best come first, which lets
+    /// alpha-beta pruning cut off more of the search.
+    ///
+    /// Captures come first, taking the most valuable victim with the least
+    /// valuable attacker first, then promotions, then quiet moves in the
+    /// order they were given.
+    fn order_moves(&self, moves: Vec<Move>) -> Vec<Move>;
+
+    /// Get the value in centipawns of the piece a move captures, or zero
+    /// if the move doesn't capture anything.
+    fn captured_value_cp(&self, m: Move) -> i32;
+
+    /// Get the static exchange evaluation of a move in centipawns.
+    ///
+    /// This is the material the current player nets from the exchange on
+    /// the destination square, assuming both sides keep recapturing with
+    /// their least valuable attacker for as long as it pays off.
+    /// A negative value means the capture loses material, and a move that
+    /// doesn't capture anything is worth 0.
+    fn see(&self, m: Move) -> i32 {
+        self.see_with(m, &self.piece_values())
+    }
+
+    /// Get the static exchange evaluation of a move in centipawns,
+    /// counting the pieces traded with the given piece values.
+    fn see_with(&self, m: Move, values: &PieceValues) -> i32;
+
+    /// Get the value of the board for a player without any lookahead, blending
+    /// each heuristic by its weight in `engine`.
+    ///
+    /// `engine` may be an `EvalEngine`, or an `Option<[f64; 6]>` of weights
+    /// in the order of its first six fields: piece tables, mobility, naive
+    /// material, control, closest enemy to the king, and trades. An engine
+    /// of `None` only uses the piece tables.
+    fn static_eval(&self, color: Color, engine: impl Into<EvalEngine>) -> f64 {
+        let engine = engine.into();
+        let mut eval = 0.0;
+
+        if engine.material != 0.0 {
+            eval += self.value_for(color) * engine.material
+        }
+        if engine.mobility != 0.0 {
+            eval += self.mobility_value_for(color) * engine.mobility
+        }
+        if engine.naive != 0.0 {
+            eval += self.naive_value_for(color) * engine.naive
+        }
+        if engine.control != 0.0 {
+            eval += self.control_value_for(color) * engine.control
+        }
+        if engine.closest != 0.0 {
+            eval += self.closest_value_for(color) * engine.closest
+        }
+        if engine.trade != 0.0 {
+            eval += self.trade_value_for(color) * engine.trade
+        }
+        if engine.trapped_pieces != 0.0 {
+            eval += self.trapped_pieces_value_for(color) * engine.trapped_pieces
+        }
+        if engine.material_imbalance != 0.0 {
+            eval += self.material_imbalance_value_for(color) * engine.material_imbalance
+        }
+        if engine.rook_behind_passer != 0.0 {
+            eval += self.rook_behind_passer_value_for(color) * engine.rook_behind_passer
+        }
+        if engine.development != 0.0 {
+            eval += self.development_value_for(color) * engine.development
+        }
+        eval
+    }
+
+    /// Get the static evaluation of the board in whole centipawns.
+    ///
+    /// One pawn is worth ten points in the piece tables, so a point is
+    /// `CENTIPAWNS_PER_POINT` centipawns.
+    fn static_eval_cp(&self, color: Color, engine: impl Into<EvalEngine>) -> i32 {
+        (self.static_eval(color, engine) * CENTIPAWNS_PER_POINT).round() as i32
+    }
+
+    /// Get the best move for the current player with `depth` number of moves
+    /// of lookahead, scoring positions in integer centipawns.
+    ///
+    /// This works like `get_best_next_move`, but every comparison is made on
+    /// whole centipawns, and the root moves are searched in order on a single
+    /// thread with `pvs`, so the same position always produces the same move.
+    /// Mates are scored as `MATE_SCORE_CP` minus the number of plies to the mate.
+    ///
+    /// This method returns
+    /// 1. The best move
+    /// 2. The number of boards evaluated to come to a conclusion
+    /// 3. The rating of the best move, in centipawns
+    fn get_best_next_move_cp(&self, depth: i32, engine: impl Into<EvalEngine>) -> (Move, u64, i32) {
+        let engine = engine.into();
+        let legal_moves = self.get_legal_moves();
+
+        let mut tt = DashMap::new();
+        let mut board_count = 0;
+        let mut alpha = -MATE_SCORE_CP - 1;
+        let beta = MATE_SCORE_CP + 1;
+
+        if legal_moves.is_empty() {
+            return (Move::Resign, 0, if self.eval_is_checkmate() { -MATE_SCORE_CP } else { 0 });
+        } else if let [only] = legal_moves[..] {
+            // there's nothing to choose between, but the move is still scored
+            let child = self.apply_eval_move(only);
+            let value = -child.pvs_at(depth, -beta, -alpha, engine, &mut tt, 1, &mut board_count);
+            return (only, board_count, value);
+        }
+        let mut best: Option<(Move, i32)> = None;
+
+        for m in &legal_moves {
+            let child = self.apply_eval_move(*m);
+            let value = if best.is_none() {
+                -child.pvs_at(depth, -beta, -alpha, engine, &mut tt, 1, &mut board_count)
+            } else {
+                // a move that only ties the best so far fails low here, so
+                // the first of several equally good moves is kept
+                let value = -child.pvs_at(depth, -alpha - 1, -alpha, engine, &mut tt, 1, &mut board_count);
+                if value > alpha {
+                    -child.pvs_at(depth, -beta, -alpha, engine, &mut tt, 1, &mut board_count)
+                } else {
+                    value
+                }
+            };
+            if best.is_none_or(|(_, best_value)| value > best_value) {
+                best = Some((*m, value));
+                alpha = value;
+            }
+        }
+
+        match best {
+            Some((best_move, value)) => (best_move, board_count, value),
+            None => unreachable!("there is at least one legal move"),
+        }
+    }
+
+    /// Search for the best move for the current player with `depth` number
+    /// of moves of lookahead, the same way as `get_best_next_move_cp`.
+    ///
+    /// Forced mates are reported as `Score::Mate` with the number of moves
+    /// to the mate instead of as a centipawn score.
+    fn search(&self, depth: i32, engine: impl Into<EvalEngine>) -> SearchResult {
+        let (best_move, nodes, score) = self.get_best_next_move_cp(depth, engine);
+        SearchResult {
+            best_move,
+            score: Score::from_cp(score),
+            nodes,
+            split: None,
+        }
+    }
+
+    /// Perform minimax on a certain position using integer centipawn scores.
+    ///
+    /// `ply` is the distance from the root of the search, which is used to
+    /// prefer faster mates over slower ones.
+    #[allow(clippy::too_many_arguments)]
+    fn minimax_cp(
+        &self,
+        depth: i32,
+        mut alpha: i32,
+        mut beta: i32,
+        is_maximizing: bool,
+        getting_move_for: Color,
+        board_count: &mut u64,
+        engine: impl Into<EvalEngine>,
+        ply: i32,
+    ) -> i32 {
+        let engine = engine.into();
+        if depth == 0 {
+            *board_count += 1;
+            return self.static_eval_cp(getting_move_for, engine);
+        }
+
+        let legal_moves = self.order_moves(self.get_legal_moves());
+        if legal_moves.is_empty() {
+            return if !self.eval_is_checkmate() {
+                0
+            } else if is_maximizing {
+                -(MATE_SCORE_CP - ply)
+            } else {
+                MATE_SCORE_CP - ply
+            };
+        } else if self.eval_is_stalemate() {
+            return 0;
+        }
+
+        let mut best_move_value = if is_maximizing {
+            -MATE_SCORE_CP - 1
+        } else {
+            MATE_SCORE_CP + 1
+        };
+        for m in &legal_moves {
+            let child_board_value = self.apply_eval_move(*m).minimax_cp(
+                depth - 1,
+                alpha,
+                beta,
+                !is_maximizing,
+                getting_move_for,
+                board_count,
+                engine,
+                ply + 1,
+            );
+
+            if is_maximizing {
+                best_move_value = best_move_value.max(child_board_value);
+                alpha = alpha.max(best_move_value);
+            } else {
+                best_move_value = best_move_value.min(child_board_value);
+                beta = beta.min(best_move_value);
+            }
+
+            if beta <= alpha {
+                break;
+            }
+        }
+
+        best_move_value
+    }
+
+    /// Search this position `depth` plies deep with principal variation
+    /// search, and get its value in centipawns for the current player.
+    ///
+    /// The first move at every node is searched with the full window, and
+    /// the rest with a null window that only asks whether they beat it. A
+    /// move that does is searched again with the full window. With good
+    /// move ordering most moves fail the null window search quickly, so
+    /// this visits fewer positions than `minimax_cp` for the same score.
+    ///
+    /// Every searched position is stored in `tt`, and its best move is
+    /// searched first when the position comes up again. A stored score is
+    /// only reused for a search to the same depth, so the result is always
+    /// what a plain alpha-beta search to `depth` would find.
+    fn pvs(
+        &self,
+        depth: i32,
+        alpha: i32,
+        beta: i32,
+        engine: impl Into<EvalEngine>,
+        tt: &mut DashMap<u64, TtEntry>,
+    ) -> i32 {
+        self.pvs_at(depth, alpha, beta, engine, tt, 0, &mut 0)
+    }
+
+    /// Perform `pvs` on a position `ply` plies from the root of the search,
+    /// which is used to prefer faster mates over slower ones, adding the
+    /// number of boards evaluated to `board_count`.
+    #[allow(clippy::too_many_arguments)]
+    fn pvs_at(
+        &self,
+        depth: i32,
+        mut alpha: i32,
+        beta: i32,
+        engine: impl Into<EvalEngine>,
+        tt: &mut DashMap<u64, TtEntry>,
+        ply: i32,
+        board_count: &mut u64,
+    ) -> i32 {
+        let engine = engine.into();
+        if depth == 0 {
+            *board_count += 1;
+            // rate the position for the player at the root, like `minimax_cp`,
+            // since not every evaluation term is the same for both sides
+            let to_move = self.get_current_player_color();
+            return if ply % 2 == 0 {
+                self.static_eval_cp(to_move, engine)
+            } else {
+                -self.static_eval_cp(!to_move, engine)
+            };
+        }
+
+        let mut legal_moves = self.order_moves(self.get_legal_moves());
+        if legal_moves.is_empty() {
+            return if self.eval_is_checkmate() { -(MATE_SCORE_CP - ply) } else { 0 };
+        } else if self.eval_is_stalemate() {
+            return 0;
+        }
+
+        let hash = self.zobrist_hash();
+        if let Some(entry) = tt.get(&hash).map(|entry| *entry) {
+            if entry.depth == depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+            if let Some(i) = legal_moves.iter().position(|m| *m == entry.best_move) {
+                let m = legal_moves.remove(i);
+                legal_moves.insert(0, m);
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut best_value = -MATE_SCORE_CP - 1;
+        let mut best_move = legal_moves[0];
+        for (i, m) in legal_moves.iter().enumerate() {
+            let child = self.apply_eval_move(*m);
+            let value = if i == 0 {
+                -child.pvs_at(depth - 1, -beta, -alpha, engine, tt, ply + 1, board_count)
+            } else {
+                let value = -child.pvs_at(depth - 1, -alpha - 1, -alpha, engine, tt, ply + 1, board_count);
+                if alpha < value && value < beta {
+                    -child.pvs_at(depth - 1, -beta, -alpha, engine, tt, ply + 1, board_count)
+                } else {
+                    value
+                }
+            };
+
+            if value > best_value {
+                best_value = value;
+                best_move = *m;
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_value <= original_alpha {
+            Bound::Upper
+        } else if best_value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        tt.insert(
+            hash,
+            TtEntry {
+                depth,
+                score: score_to_tt(best_value, ply),
+                bound,
+                best_move,
+            },
+        );
+        best_value
+    }
+
+    /// Search only the captures from this position until it is quiet, and
+    /// get its value in centipawns for the current player.
+    ///
+    /// The current player may always stand pat instead of capturing, so the
+    /// result is never below the static evaluation. `config` controls which
+    /// captures are skipped to keep the capture tree from exploding, and
+    /// how many plies of captures are searched at most.
+    fn quiescence_cp(
+        &self,
+        mut alpha: i32,
+        beta: i32,
+        board_count: &mut u64,
+        engine: impl Into<EvalEngine>,
+        config: &QuiescenceConfig,
+    ) -> i32 {
+        let engine = engine.into();
+        *board_count += 1;
+        let stand_pat = self.static_eval_cp(self.get_current_player_color(), engine);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        if config.max_qdepth == 0 {
+            return alpha;
+        }
+
+        let child_config = QuiescenceConfig {
+            max_qdepth: config.max_qdepth - 1,
+            ..*config
+        };
+        for m in self.get_capture_moves() {
+            if config.see_pruning && self.see(m) < 0 {
+                continue;
+            }
+            if let (Some(margin), Move::Piece(..)) = (config.delta_margin, m) {
+                if stand_pat + self.captured_value_cp(m) + margin <= alpha {
+                    continue;
+                }
+            }
+
+            let value = -self
+                .apply_eval_move(m)
+                .quiescence_cp(-beta, -alpha, board_count, engine, &child_config);
+            if value >= beta {
+                return value;
+            }
+            alpha = alpha.max(value);
+        }
+
+        alpha
+    }
+
+    /// Search only the captures and promotions from this position until it is
+    /// quiet, and get its value for `color`.
+    ///
+    /// Stopping the search in the middle of an exchange would rate the position
+    /// as if the last capture could never be answered, so `minimax` calls this
+    /// at its leaves instead of evaluating them directly. The player to move may
+    /// always stand pat instead of capturing. This uses the default
+    /// `QuiescenceConfig`.
+    fn quiescence(&self, alpha: f64, beta: f64, color: Color, engine: impl Into<EvalEngine>) -> f64 {
+        self.quiescence_with(alpha, beta, color, engine, &QuiescenceConfig::default())
+    }
+
+    /// Search only the captures and promotions from this position until it is
+    /// quiet like `quiescence`, skipping moves according to `config`.
+    fn quiescence_with(
+        &self,
+        mut alpha: f64,
+        mut beta: f64,
+        color: Color,
+        engine: impl Into<EvalEngine>,
+        config: &QuiescenceConfig,
+    ) -> f64 {
+        let engine = engine.into();
+        let stand_pat = self.static_eval(color, engine);
+        let is_maximizing = self.get_current_player_color() == color;
+        if is_maximizing {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+        } else {
+            if stand_pat <= alpha {
+                return stand_pat;
+            }
+            beta = beta.min(stand_pat);
+        }
+        if config.max_qdepth == 0 {
+            return stand_pat;
+        }
+
+        let child_config = QuiescenceConfig {
+            max_qdepth: config.max_qdepth - 1,
+            ..*config
+        };
+        let mut best_move_value = stand_pat;
+        for m in self.order_moves(self.get_noisy_moves()) {
+            if config.see_pruning && self.see(m) < 0 {
+                continue;
+            }
+            // promotions gain more than any capture, so they're never skipped
+            let captured = self.captured_value_cp(m);
+            if let (Some(margin), true) = (config.delta_margin, captured > 0) {
+                let gain = (captured + margin) as f64 / CENTIPAWNS_PER_POINT;
+                if (is_maximizing && stand_pat + gain <= alpha)
+                    || (!is_maximizing && stand_pat - gain >= beta)
+                {
+                    continue;
+                }
+            }
+
+            let value = self
+                .apply_eval_move(m)
+                .quiescence_with(alpha, beta, color, engine, &child_config);
+            if is_maximizing {
+                best_move_value = best_move_value.max(value);
+                alpha = alpha.max(value);
+            } else {
+                best_move_value = best_move_value.min(value);
+                beta = beta.min(value);
+            }
+            if beta <= alpha {
+                break;
+            }
+        }
+
+        best_move_value
+    }
+
     /// Get the best move for the current player with `depth` number of moves
     /// of lookahead.
     ///
@@ -289,17 +960,23 @@ pub trait Evaluate: Sized where Self: Sync {
     ///
     /// It's best not to use the rating value by itself for anything, as it
     /// is relative to the other player's move ratings as well.
-    fn get_best_next_move(&self, depth: i32, engine: Option<[f64; 6]>) -> (Move, u64, f64) {
+    ///
+    /// If the current player has no legal moves, the move is `Move::Resign`,
+    /// rated `-MATE_VALUE` when checkmated and `0.0` when stalemated.
+    fn get_best_next_move(&self, depth: i32, engine: impl Into<EvalEngine>) -> (Move, u64, f64) {
+        let engine = engine.into();
         let legal_moves = self.get_legal_moves();        
 
-        if legal_moves.len() == 1 {
+        if legal_moves.is_empty() {
+            return (Move::Resign, 0, if self.eval_is_checkmate() { -MATE_VALUE } else { 0.0 })
+        } else if legal_moves.len() == 1 {
             return (legal_moves[0], 0, 0.0)
         }
 
         let color = self.get_current_player_color();
 
         let board_count = Arc::new(Mutex::new(0));
-        let board_cache: Arc<Mutex<DashMap<String, f64>>> = Arc::new(Mutex::new(DashMap::new()));
+        let board_cache: Arc<Mutex<DashMap<u64, f64>>> = Arc::new(Mutex::new(DashMap::new()));
 
         let arc_engine = Arc::new(engine);
 
@@ -319,6 +996,7 @@ pub trait Evaluate: Sized where Self: Sync {
                 &mut bc.lock().unwrap(),
                 *e,
                 &mut c.lock().unwrap(),
+                &mut vec![self.zobrist_hash()],
             );
             //println!("Move {}: {}", mov.to_string(), value.to_string());
             (mov, value)
@@ -348,23 +1026,29 @@ pub trait Evaluate: Sized where Self: Sync {
         (*best_move, count, best_move_value)
     }
 
-    /// Get the best move for the current player with `depth` number of moves
-    /// of lookahead.
+    /// Get the worst move for the current player with `depth` number of moves
+    /// of lookahead: the move whose resulting position rates lowest for them.
+    /// This is useful for deliberately weak sparring engines.
     ///
     /// This method returns
-    /// 1. The best move
+    /// 1. The worst move
     /// 2. The number of boards evaluated to come to a conclusion
-    /// 3. The rating of the best move
+    /// 3. The rating of the worst move
     ///
     /// It's best not to use the rating value by itself for anything, as it
     /// is relative to the other player's move ratings as well.
-    fn get_worst_next_move(&self, depth: i32, engine: Option<[f64; 6]>) -> (Move, u64, f64) {
+    fn get_worst_next_move(&self, depth: i32, engine: impl Into<EvalEngine>) -> (Move, u64, f64) {
+        let engine = engine.into();
         let legal_moves = self.get_legal_moves();        
 
+        if legal_moves.is_empty() {
+            return (Move::Resign, 0, if self.eval_is_checkmate() { -MATE_VALUE } else { 0.0 })
+        }
+
         let color = self.get_current_player_color();
 
         let board_count = Arc::new(Mutex::new(0));
-        let board_cache: Arc<Mutex<DashMap<String, f64>>> = Arc::new(Mutex::new(DashMap::new()));
+        let board_cache: Arc<Mutex<DashMap<u64, f64>>> = Arc::new(Mutex::new(DashMap::new()));
 
         let arc_engine = Arc::new(engine);
         
@@ -384,10 +1068,11 @@ pub trait Evaluate: Sized where Self: Sync {
                 &mut bc.lock().unwrap(),
                 *e,
                 &mut c.lock().unwrap(),
+                &mut vec![self.zobrist_hash()],
             );
             (mov, value)
         })
-        .max_by(|(_, a), (_, b)| a.partial_cmp(&b).unwrap_or(Ordering::Equal))
+        .min_by(|(_, a), (_, b)| a.partial_cmp(&b).unwrap_or(Ordering::Equal))
         .unwrap_or(
     if legal_moves.len() > 0 {
                 (&legal_moves[0], 0.0)
@@ -400,6 +1085,121 @@ pub trait Evaluate: Sized where Self: Sync {
         (*best_move, count, best_move_value)
     }
 
+    /// Get the best move for the current player within a time budget, searching one
+    /// ply deeper each time the previous depth finishes.
+    ///
+    /// This returns the same three values as `get_best_next_move`, taken from the
+    /// deepest search that finished. A depth that `next_depth_fits` expects to
+    /// run past `max` isn't started, and a depth still running when `max` has passed
+    /// is thrown away, except for the leaf evaluations it cached, which the later
+    /// depths reuse. The shallowest search always finishes, so a legal move is
+    /// returned however small the budget is.
+    fn get_best_move_timed(&self, max: Duration, engine: impl Into<EvalEngine>) -> (Move, u64, f64) {
+        let engine = engine.into();
+        let legal_moves = self.get_legal_moves();
+
+        if legal_moves.is_empty() {
+            return (Move::Resign, 0, if self.eval_is_checkmate() { -MATE_VALUE } else { 0.0 })
+        } else if legal_moves.len() == 1 {
+            return (legal_moves[0], 0, 0.0)
+        }
+
+        let deadline = Instant::now() + max;
+        let board_cache: Arc<Mutex<DashMap<u64, f64>>> = Arc::new(Mutex::new(DashMap::new()));
+        let mut best = (legal_moves[0], 0, f64::MIN);
+        let mut iteration_times = vec![];
+
+        for depth in 0.. {
+            // the first depth has no deadline, so there's always a move to return
+            let depth_deadline = if depth == 0 { None } else { Some(deadline) };
+            let depth_start = Instant::now();
+            if !next_depth_fits(&iteration_times, deadline.saturating_duration_since(depth_start)) {
+                break;
+            }
+            match self.search_root_until(&legal_moves, depth, engine, &board_cache, depth_deadline) {
+                Some(result) => best = result,
+                None => break,
+            }
+            iteration_times.push(depth_start.elapsed());
+            // a forced mate can't be improved on by searching deeper
+            if best.2.abs() >= MATE_VALUE || Instant::now() >= deadline {
+                break;
+            }
+        }
+
+        best
+    }
+
+    /// Get the best move and its rating at every depth from 0 to `max_depth`,
+    /// searching one ply deeper each time like `get_best_move_timed` does.
+    ///
+    /// This is meant for analysis: a best move that keeps changing from one depth
+    /// to the next usually points at the evaluation or the pruning. Each entry is
+    /// the depth followed by the move and rating `get_best_next_move` would give at
+    /// that depth. The list is empty if the current player has no legal moves.
+    fn best_move_at_each_depth(&self, max_depth: i32, engine: impl Into<EvalEngine>) -> Vec<(i32, Move, f64)> {
+        let engine = engine.into();
+        let legal_moves = self.get_legal_moves();
+        let board_cache: Arc<Mutex<DashMap<u64, f64>>> = Arc::new(Mutex::new(DashMap::new()));
+
+        if legal_moves.is_empty() {
+            return vec![];
+        }
+
+        (0..=max_depth)
+            .map(|depth| {
+                let (m, _, value) = self
+                    .search_root_until(&legal_moves, depth, engine, &board_cache, None)
+                    .expect("a search without a deadline always finishes");
+                (depth, m, value)
+            })
+            .collect()
+    }
+
+    /// Search each of `legal_moves` with `depth` number of moves of lookahead, and get
+    /// the best one along with the number of boards evaluated and its rating.
+    ///
+    /// Leaf evaluations go into `board_cache`, so passing the same cache to each depth
+    /// of an iterative search lets the deeper searches reuse them. This gives `None`
+    /// if `deadline` passes before every move has been searched.
+    fn search_root_until(
+        &self,
+        legal_moves: &[Move],
+        depth: i32,
+        engine: impl Into<EvalEngine>,
+        board_cache: &Arc<Mutex<DashMap<u64, f64>>>,
+        deadline: Option<Instant>,
+    ) -> Option<(Move, u64, f64)> {
+        let engine = engine.into();
+        let color = self.get_current_player_color();
+        let board_count = Arc::new(Mutex::new(0));
+
+        let values: Vec<(Move, f64)> = legal_moves
+            .par_iter()
+            .map(|mov| {
+                let value = self.apply_eval_move(*mov).minimax_until(
+                    depth,
+                    -1000000.0,
+                    1000000.0,
+                    false,
+                    color,
+                    &mut board_count.lock().unwrap(),
+                    engine,
+                    &mut board_cache.lock().unwrap(),
+                    &mut vec![self.zobrist_hash()],
+                    deadline,
+                )?;
+                Some((*mov, value))
+            })
+            .collect::<Option<_>>()?;
+
+        let count: u64 = *board_count.lock().unwrap();
+        values
+            .into_iter()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(m, value)| (m, count, value))
+    }
+
     /// Perform minimax on a certain position, and get the minimum or maximum value
     /// for a board. To get the best move, you minimize the values of the possible outcomes from your
     /// own position, and maximize the values of the replies made by the other player.
@@ -407,7 +1207,43 @@ pub trait Evaluate: Sized where Self: Sync {
     /// In other words, choose moves with the assumption that your opponent will make the
     /// best possible replies to your moves. Moves that are seemingly good, but are easily countered,
     /// are categorically eliminated by this algorithm.
+    ///
+    /// `path` holds the Zobrist hashes of the positions from the root of the search
+    /// down to this one's parent. A position that is already on it scores as a draw.
+    #[allow(clippy::too_many_arguments)]
     fn minimax(
+        &self,
+        depth: i32,
+        alpha: f64,
+        beta: f64,
+        is_maximizing: bool,
+        getting_move_for: Color,
+        board_count: &mut u64,
+        engine: impl Into<EvalEngine>,
+        cache: &mut DashMap<u64, f64>,
+        path: &mut Vec<u64>,
+    ) -> f64 {
+        self.minimax_until(
+            depth,
+            alpha,
+            beta,
+            is_maximizing,
+            getting_move_for,
+            board_count,
+            engine,
+            cache,
+            path,
+            None,
+        )
+        .expect("a search without a deadline always finishes")
+    }
+
+    /// Perform minimax like `minimax`, but give up once `deadline` passes.
+    ///
+    /// This returns `None` if the search was cut short, in which case the values in
+    /// `cache` are still good but `path` is left partly filled.
+    #[allow(clippy::too_many_arguments)]
+    fn minimax_until(
         &self,
         depth: i32,
         mut alpha: f64,
@@ -415,88 +1251,96 @@ pub trait Evaluate: Sized where Self: Sync {
         is_maximizing: bool,
         getting_move_for: Color,
         board_count: &mut u64,
-        engine: Option<[f64; 6]>,
-        mut cache: &mut DashMap<String, f64>,
-    ) -> f64 {        
-        let eval_engine = match engine {
-            Some(a) => a,
-            None => [1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-        };
-        if depth == 0 {
-            *board_count += 1;
-            let mut eval = 0.0;
+        engine: impl Into<EvalEngine>,
+        mut cache: &mut DashMap<u64, f64>,
+        path: &mut Vec<u64>,
+        deadline: Option<Instant>,
+    ) -> Option<f64> {
+        let engine = engine.into();
 
-            if eval_engine[0] != 0.0 {
-                eval += self.value_for(getting_move_for) * eval_engine[0]
-            }
-            if eval_engine[1] != 0.0 {
-                eval += self.mobility_value_for(getting_move_for) * eval_engine[1]
-            }
-            if eval_engine[2] != 0.0 {
-                eval += self.naive_value_for(getting_move_for) * eval_engine[2]
-            }
-            if eval_engine[3] != 0.0 {
-                eval += self.control_value_for(getting_move_for) * eval_engine[3]
-            }
-            if eval_engine[4] != 0.0 {
-                eval += self.closest_value_for(getting_move_for) * eval_engine[4]
-            }
-            if eval_engine[5] != 0.0 {
-                eval += self.trade_value_for(getting_move_for) * eval_engine[5]
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return None
+        }
+
+        //Going back to a position on the way here is a draw, since either
+        //player could keep repeating it
+        let hash = self.zobrist_hash();
+        if path.contains(&hash) {
+            return Some(0.0)
+        }
+
+        let legal_moves = self.order_moves(self.get_legal_moves());
+
+        //Mate and stalemate end the game, even at the horizon
+        if legal_moves.is_empty() {
+            let value = if !self.eval_is_checkmate() {
+                0.0
+            } else if self.get_current_player_color() == getting_move_for {
+                //Ally is mated, the sooner the worse
+                -MATE_VALUE - depth as f64
+            } else {
+                //Enemy is mated, the sooner the better
+                MATE_VALUE + depth as f64
+            };
+            if depth == 0 {
+                *board_count += 1;
+                cache.insert(self.zobrist_hash(), value);
             }
-            cache.insert(self.cache_repr(), eval);
-            return eval
+            return Some(value)
+        }
+
+        if depth == 0 {
+            *board_count += 1;
+            // searched with a full window, so the cached value is exact
+            let eval = self.quiescence(-1000000.0, 1000000.0, getting_move_for, engine);
+            cache.insert(self.zobrist_hash(), eval);
+            return Some(eval)
         }
 
-        let legal_moves = self.get_legal_moves();
         let mut best_move_value;
 
         //Check if search in this branch must stop
-        if self.eval_is_checkmate() {
-            if is_maximizing {
-                //Enemy is mated! Most Chess experts reccomend doing this
-                return f64::MIN + 1.0
-            }
-            else {
-                //Ally is mated, most chess experts would avoid this
-                //Adding one to avoid errored state
-                return f64::MAX
-            }
-        }
-        else if self.eval_is_stalemate() {
+        if self.eval_is_stalemate() {
             //We want to avoid stalemate unless the situation is dire 
             if is_maximizing {
-                return -15.0
+                return Some(-15.0)
             }
             else {
-                return 15.0
+                return Some(15.0)
             }
         }
-        else if legal_moves.len() == 0 {
-            //Should never reach here, but if it does we should avoid it            
-            return f64::MIN;
-        }
 
+        path.push(hash);
         if is_maximizing {
             best_move_value = -999999.0;
 
             for m in &legal_moves {
+                let child_board = self.apply_eval_move(*m);
+                // only leaves are cached, so a cached value is only the
+                // child's value when the child is searched as a leaf too
+                let child_hash = child_board.zobrist_hash();
+                let cached = if depth == 1 && !path.contains(&child_hash) {
+                    cache.get(&child_hash).map(|value| *value)
+                } else {
+                    None
+                };
                 let child_board_value;
-                let repr = self.cache_repr();
-                if cache.contains_key(&repr) {
-                    child_board_value = *cache.get(&repr).unwrap();
+                if let Some(value) = cached {
+                    child_board_value = value;
                 }
                 else {
-                    child_board_value = self.apply_eval_move(*m).minimax(
+                    child_board_value = child_board.minimax_until(
                         depth - 1,
                         alpha,
                         beta,
                         !is_maximizing,
                         getting_move_for,
                         board_count,
-                        Some(eval_engine),
-                        &mut cache
-                    );
+                        engine,
+                        &mut cache,
+                        path,
+                        deadline
+                    )?;
                 }
                 if child_board_value > best_move_value {
                     best_move_value = child_board_value;
@@ -507,29 +1351,39 @@ pub trait Evaluate: Sized where Self: Sync {
                 }
 
                 if beta <= alpha {
-                    return best_move_value;
+                    break;
                 }
             }
         } else {
             best_move_value = 999999.0;
 
             for m in &legal_moves {
+                let child_board = self.apply_eval_move(*m);
+                // only leaves are cached, so a cached value is only the
+                // child's value when the child is searched as a leaf too
+                let child_hash = child_board.zobrist_hash();
+                let cached = if depth == 1 && !path.contains(&child_hash) {
+                    cache.get(&child_hash).map(|value| *value)
+                } else {
+                    None
+                };
                 let child_board_value;
-                let repr = self.cache_repr();
-                if cache.contains_key(&repr) {
-                    child_board_value = *cache.get(&repr).unwrap()
+                if let Some(value) = cached {
+                    child_board_value = value;
                 }
                 else {
-                    child_board_value = self.apply_eval_move(*m).minimax(
+                    child_board_value = child_board.minimax_until(
                         depth - 1,
                         alpha,
                         beta,
                         !is_maximizing,
                         getting_move_for,
                         board_count,
-                        Some(eval_engine),
-                        &mut cache
-                    );
+                        engine,
+                        &mut cache,
+                        path,
+                        deadline
+                    )?;
                 }
                 if child_board_value < best_move_value {
                     best_move_value = child_board_value;
@@ -540,11 +1394,647 @@ pub trait Evaluate: Sized where Self: Sync {
                 }
 
                 if beta <= alpha {
-                    return best_move_value;
+                    break;
                 }
             }
         }
+        path.pop();
 
-        best_move_value
+        Some(best_move_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// White mates with `Ra8#` behind Black's unmoved kingside pawns.
+    pub(crate) fn back_rank_mate() -> Board {
+        Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap()
+    }
+
+    fn hanging_piece_positions() -> Vec<Board> {
+        vec![
+            // the queen can take an undefended rook
+            BoardBuilder::default()
+                .piece(Piece::King(WHITE, G1))
+                .piece(Piece::Queen(WHITE, D1))
+                .piece(Piece::Pawn(WHITE, G2))
+                .piece(Piece::King(BLACK, H7))
+                .piece(Piece::Rook(BLACK, D6))
+                .piece(Piece::Pawn(BLACK, G7))
+                .build(),
+            // a pawn can take a knight
+            BoardBuilder::default()
+                .piece(Piece::King(WHITE, E1))
+                .piece(Piece::Pawn(WHITE, E4))
+                .piece(Piece::Pawn(WHITE, A2))
+                .piece(Piece::King(BLACK, E8))
+                .piece(Piece::Knight(BLACK, D5))
+                .piece(Piece::Pawn(BLACK, H7))
+                .build(),
+            // black's bishop can take the white queen
+            BoardBuilder::default()
+                .piece(Piece::King(WHITE, A1))
+                .piece(Piece::Queen(WHITE, F3))
+                .piece(Piece::King(BLACK, H8))
+                .piece(Piece::Bishop(BLACK, B7))
+                .piece(Piece::Pawn(BLACK, H7))
+                .set_turn(BLACK)
+                .build(),
+        ]
+    }
+
+    #[test]
+    fn test_centipawn_search_matches_float_search() {
+        for board in hanging_piece_positions() {
+            let (float_move, _, _) = board.get_best_next_move(1, None);
+            let (cp_move, _, _) = board.get_best_next_move_cp(1, None);
+            assert_eq!(float_move, cp_move, "{}", board.fen());
+        }
+    }
+
+    #[test]
+    fn test_pvs_matches_alpha_beta() {
+        let mut positions = hanging_piece_positions();
+        positions.push(Board::default());
+        positions.push(
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(),
+        );
+        // back rank mate in one, and a queen sacrifice for mate in two
+        positions.push(back_rank_mate());
+        positions.push(Board::from_fen("6rk/6pp/8/6N1/8/8/8/1Q4K1 w - - 0 1").unwrap());
+
+        for board in &positions {
+            for depth in 1..=2 {
+                let color = board.get_current_player_color();
+                let window = (-MATE_SCORE_CP - 1, MATE_SCORE_CP + 1);
+                let expected = board.minimax_cp(depth + 1, window.0, window.1, true, color, &mut 0, None, 0);
+                assert_eq!(board.get_best_next_move_cp(depth, None).2, expected);
+                let mut tt = DashMap::new();
+                let value = board.pvs(depth + 1, window.0, window.1, None, &mut tt);
+                assert_eq!(value, expected, "depth {} of {}", depth, board.fen());
+                // searching again reuses every stored score
+                assert_eq!(board.pvs(depth + 1, window.0, window.1, None, &mut tt), expected);
+                let root = *tt.get(&board.zobrist_hash()).unwrap();
+                assert_eq!(root.bound, Bound::Exact);
+                assert_eq!(root.score, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_move_to_san() {
+        let board = Board::from_fen("4k3/8/8/3p4/4P3/8/7K/R6R w - - 0 1").unwrap();
+        assert_eq!(Move::Piece(E4, D5).to_san(&board), "exd5");
+        assert_eq!(Move::Piece(E4, E5).to_san(&board), "e5");
+        // both rooks can reach the squares between them
+        assert_eq!(Move::Piece(A1, D1).to_san(&board), "Rad1");
+        assert_eq!(Move::Piece(H1, D1).to_san(&board), "Rhd1");
+        assert_eq!(Move::Piece(A1, B1).to_san(&board), "Rab1");
+        assert_eq!(Move::Piece(H1, H8).to_san(&board), "Rh8+");
+        assert_eq!(Move::Piece(A1, A8).to_san(&board), "Ra8+");
+
+        let board = back_rank_mate();
+        assert_eq!(Move::Piece(A1, A8).to_san(&board), "Ra8#");
+        assert_eq!(Move::KingSideCastle.to_san(&Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap()), "O-O");
+
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(Move::Piece(B7, B8).to_san(&board), "b8=Q+");
+        // reading it back gives a promotion to a queen, which plays the same
+        for m in board.get_legal_moves() {
+            let parsed = Move::from_san(&m.to_san(&board), &board).unwrap();
+            assert_eq!(board.apply_eval_move(parsed), board.apply_eval_move(m), "{}", m);
+        }
+    }
+
+    #[test]
+    fn test_move_uci() {
+        let offboard = Position::new(-1, -1);
+        let cases = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", Move::Piece(E2, E4), "e2e4"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", Move::KingSideCastle, "e1g1"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", Move::QueenSideCastle, "e1c1"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", Move::KingSideCastle, "e8g8"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", Move::QueenSideCastle, "e8c8"),
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", Move::Piece(E5, D6), "e5d6"),
+            ("3k4/4P3/8/8/8/8/8/4K3 w - - 0 1", Move::Promotion(E7, E8, Piece::Queen(WHITE, offboard)), "e7e8q"),
+            ("3k4/4P3/8/8/8/8/8/4K3 w - - 0 1", Move::Promotion(E7, E8, Piece::Knight(WHITE, offboard)), "e7e8n"),
+            ("4k3/8/8/8/8/8/4p3/3K4 b - - 0 1", Move::Promotion(E2, E1, Piece::Rook(BLACK, offboard)), "e2e1r"),
+            ("4k3/8/8/8/8/8/4p3/3K4 b - - 0 1", Move::Promotion(E2, E1, Piece::Bishop(BLACK, offboard)), "e2e1b"),
+        ];
+        for (fen, m, uci) in cases {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(m.to_uci(&board), uci);
+            assert_eq!(Move::from_uci(uci, &board), Ok(m));
+        }
+
+        // a king that isn't on its starting square just moves
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/3K4 w - - 0 1").unwrap();
+        assert_eq!(Move::from_uci("d1f1", &board), Ok(Move::Piece(D1, F1)));
+        assert!(Move::from_uci("e2e4e", &board).is_err());
+        assert!(Move::from_uci("e2", &board).is_err());
+        assert!(Move::from_uci("z2e4", &board).is_err());
+    }
+
+    #[test]
+    fn test_move_from_san() {
+        let board = Board::default();
+        assert_eq!(Move::from_san("Nf3", &board), Ok(Move::Piece(G1, F3)));
+        assert_eq!(Move::from_san("e4", &board), Ok(Move::Piece(E2, E4)));
+        assert!(Move::from_san("Nd4", &board).is_err());
+        assert!(Move::from_san("e5", &board).is_err());
+        assert!(Move::from_san("Zz9", &board).is_err());
+
+        let board = Board::from_fen("r3k2r/1P2q3/8/3p4/4Q3/2N5/8/R3K2R w KQkq - 0 1").unwrap();
+        let cases = [
+            ("Nxd5", Move::Piece(C3, D5)),
+            ("Qxe7+", Move::Piece(E4, E7)),
+            ("Qxe7#", Move::Piece(E4, E7)),
+            // the queen is pinned to the e-file
+            ("Qe6", Move::Piece(E4, E6)),
+            ("Rab1", Move::Piece(A1, B1)),
+            ("Rhf1", Move::Piece(H1, F1)),
+            ("Kf2", Move::Piece(E1, F2)),
+            ("O-O", Move::KingSideCastle),
+            ("0-0-0", Move::QueenSideCastle),
+            ("bxa8=Q", Move::Promotion(B7, A8, Piece::Queen(WHITE, Position::new(-1, -1)))),
+            ("bxa8N", Move::Promotion(B7, A8, Piece::Knight(WHITE, Position::new(-1, -1)))),
+            ("b8", Move::Piece(B7, B8)),
+        ];
+        for (san, m) in cases {
+            assert_eq!(Move::from_san(san, &board), Ok(m), "{}", san);
+        }
+
+        assert!(Move::from_san("Qxd5", &board).is_err());
+        assert!(Move::from_san("Nxe4", &board).is_err());
+        assert!(Move::from_san("Nxb5", &board).is_err());
+        assert!(Move::from_san("bxa8=K", &board).is_err());
+
+        // both rooks can reach d1
+        let board = Board::from_fen("4k3/8/8/8/8/8/6K1/R6R w - - 0 1").unwrap();
+        let error = Move::from_san("Rd1", &board).unwrap_err();
+        assert!(error.contains("more than one"), "{}", error);
+        assert_eq!(Move::from_san("Rad1", &board), Ok(Move::Piece(A1, D1)));
+        assert_eq!(Move::from_san("Rhd1", &board), Ok(Move::Piece(H1, D1)));
+        assert_eq!(
+            Move::from_san("R1d1", &board),
+            Err(String::from("`R1d1` could be made by more than one piece"))
+        );
+
+        // a pawn takes en passant
+        let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/B3K3 w - d6 0 2").unwrap();
+        assert_eq!(Move::from_san("exd6", &board), Ok(Move::Piece(E5, D6)));
+        assert_eq!(Move::from_san("Bd4", &board), Ok(Move::Piece(A1, D4)));
+    }
+
+    #[test]
+    fn test_eval_engine() {
+        let weights = [2.0, 0.5, 1.0, 0.0, 0.25, 3.0];
+        let engine = EvalEngine::from(weights);
+        assert_eq!(engine.control, 0.0);
+        assert_eq!(engine.closest, 0.25);
+        assert_eq!(<[f64; 6]>::from(engine), weights);
+
+        // the number of boards searched depends on how the threads share the cache
+        let board = hanging_piece_positions()[0];
+        let (m, _, value) = board.get_best_next_move(2, engine);
+        let (array_m, _, array_value) = board.get_best_next_move(2, Some(weights));
+        assert_eq!((m, value), (array_m, array_value));
+        let (m, _, value) = board.get_best_next_move(2, EvalEngine::default());
+        let (array_m, _, array_value) = board.get_best_next_move(2, None);
+        assert_eq!((m, value), (array_m, array_value));
+
+        // minimax takes either too
+        let value = board.minimax(2, -1e6, 1e6, true, WHITE, &mut 0, engine, &mut DashMap::new(), &mut vec![]);
+        let array_value =
+            board.minimax(2, -1e6, 1e6, true, WHITE, &mut 0, Some(weights), &mut DashMap::new(), &mut vec![]);
+        assert_eq!(value, array_value);
+    }
+
+    #[test]
+    fn test_worst_next_move() {
+        // the knight can take the queen that just came out to h4
+        let board =
+            Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/4P2q/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let (best_move, _, best_value) = board.get_best_next_move(1, None);
+        let (worst_move, _, worst_value) = board.get_worst_next_move(1, None);
+        assert_eq!(best_move, Move::Piece(F3, H4));
+        assert_ne!(worst_move, best_move);
+        assert!(worst_value < best_value, "{} {}", worst_value, best_value);
+    }
+
+    #[test]
+    fn test_minimax_perpetual_check() {
+        // white is well down on material, but can keep checking with
+        // Qf8+ Kh7 Qf7+ Kh8
+        let board = Board::from_fen("7k/5Q2/6pp/8/8/2r5/1r6/n3K3 w - - 0 1").unwrap();
+        let (m, _, value) = board.get_best_next_move(3, None);
+        assert_eq!(value, 0.0);
+        assert!(board.gives_check(m), "{}", m);
+
+        let value = board.apply_eval_move(Move::Piece(F7, F8)).minimax(
+            3,
+            -1000000.0,
+            1000000.0,
+            false,
+            WHITE,
+            &mut 0,
+            None,
+            &mut DashMap::new(),
+            &mut vec![board.zobrist_hash()],
+        );
+        assert_eq!(value, 0.0);
+
+        // without the root on the path, the repetition goes unnoticed
+        let value = board.apply_eval_move(Move::Piece(F7, F8)).minimax(
+            3,
+            -1000000.0,
+            1000000.0,
+            false,
+            WHITE,
+            &mut 0,
+            None,
+            &mut DashMap::new(),
+            &mut vec![],
+        );
+        assert!(value < 0.0);
+    }
+
+    #[test]
+    fn test_best_move_timed() {
+        let board =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        // when to stop is checked by `test_next_depth_fits`, without a clock
+        for budget in [0, 50, 300] {
+            let (m, _, _) = board.get_best_move_timed(Duration::from_millis(budget), None);
+            assert!(board.get_legal_moves().contains(&m), "{}", m);
+        }
+
+        let board = back_rank_mate();
+        let (m, _, value) = board.get_best_move_timed(Duration::from_secs(5), None);
+        assert_eq!(m, Move::Piece(A1, A8));
+        assert!(value >= MATE_VALUE);
+    }
+
+    #[test]
+    fn test_time_to_next_depth() {
+        let ms = Duration::from_millis;
+        assert_eq!(time_to_next_depth(&[]), Duration::ZERO);
+        assert_eq!(time_to_next_depth(&[ms(10)]), ms(60));
+        assert_eq!(time_to_next_depth(&[ms(0), ms(10)]), ms(60));
+        assert_eq!(time_to_next_depth(&[ms(2), ms(10), ms(40)]), ms(160));
+        // depths never get quicker, and never blow up without bound
+        assert_eq!(time_to_next_depth(&[ms(10), ms(5)]), ms(5));
+        assert_eq!(time_to_next_depth(&[Duration::from_micros(1), ms(1)]), ms(40));
+    }
+
+    #[test]
+    fn test_next_depth_fits() {
+        let ms = Duration::from_millis;
+        // the first depth always starts
+        assert!(next_depth_fits(&[], Duration::ZERO));
+        assert!(next_depth_fits(&[ms(10)], ms(60)));
+        assert!(!next_depth_fits(&[ms(10)], ms(59)));
+        // a depth five times slower than the last predicts another one
+        // five times slower again
+        assert!(!next_depth_fits(&[ms(100), ms(500)], ms(2000)));
+        assert!(next_depth_fits(&[ms(100), ms(500)], ms(2500)));
+    }
+
+    #[test]
+    fn test_order_moves() {
+        let board = Board::from_fen("4k3/1P6/8/3q4/2P1r3/2N5/8/7K w - - 0 1").unwrap();
+        let ordered = board.order_moves(board.get_legal_moves());
+        // the queen with the pawn and then the knight, the rook, the
+        // promotion, then everything else
+        assert_eq!(
+            ordered[..4],
+            [
+                Move::Piece(C4, D5),
+                Move::Piece(C3, D5),
+                Move::Piece(C3, E4),
+                Move::Piece(B7, B8),
+            ]
+        );
+        let mut sorted = ordered.clone();
+        sorted.sort();
+        let mut legal = board.get_legal_moves();
+        legal.sort();
+        assert_eq!(sorted, legal);
+
+        // searching in the order moves are generated took 13813 boards
+        let board =
+            Board::from_fen("r2qkb1r/ppp2ppp/2np1n2/4p1B1/2B1P1b1/2NP1N2/PPP2PPP/R2QK2R w KQkq - 0 7").unwrap();
+        let (m, count, value) = board.get_best_next_move(2, None);
+        assert_eq!(m, Move::KingSideCastle);
+        assert_eq!(value, 3.5);
+        assert!(count < 13813 / 2, "{}", count);
+    }
+
+    #[test]
+    fn test_best_move_at_each_depth() {
+        // taking the pawn loses the queen to the recapture
+        let board = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let depths = board.best_move_at_each_depth(2, None);
+        assert_eq!(depths.iter().map(|(depth, _, _)| *depth).collect::<Vec<_>>(), [0, 1, 2]);
+        for (depth, m, value) in &depths {
+            assert_ne!(*m, Move::Piece(D1, D5));
+            let (best, _, best_value) = board.get_best_next_move(*depth, None);
+            assert_eq!((*m, *value), (best, best_value));
+        }
+
+        let mated = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert!(mated.best_move_at_each_depth(3, None).is_empty());
+    }
+
+    #[test]
+    fn test_quiescence() {
+        // Nxd8 wins the queen, but Rxb1 comes right back at the horizon
+        let board = Board::from_fen("1r1q3k/6pp/4N3/8/8/8/6PP/1R3RK1 w - - 0 1").unwrap();
+        let exchange = board
+            .apply_eval_move(Move::Piece(E6, D8))
+            .apply_eval_move(Move::Piece(B8, B1));
+        let settled = exchange.apply_eval_move(Move::Piece(F1, B1));
+        assert_eq!(exchange.get_noisy_moves(), vec![Move::Piece(F1, B1)]);
+        assert_eq!(
+            exchange.quiescence(-1000000.0, 1000000.0, Color::White, None),
+            settled.static_eval(Color::White, None)
+        );
+
+        let (m, _, value) = board.get_best_next_move(1, None);
+        assert_eq!(m, Move::Piece(E6, D8));
+        assert!(value > exchange.static_eval(Color::White, None), "{}", value);
+        assert!(value > board.static_eval(Color::White, None) + 50.0, "{}", value);
+    }
+
+    #[test]
+    fn test_minimax_warm_cache() {
+        let board =
+            Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/4P2q/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let search = |cache: &mut DashMap<u64, f64>| {
+            board
+                .get_legal_moves()
+                .into_iter()
+                .map(|m| {
+                    let value = board.apply_eval_move(m).minimax(
+                        2,
+                        -1000000.0,
+                        1000000.0,
+                        false,
+                        WHITE,
+                        &mut 0,
+                        None,
+                        cache,
+                        &mut vec![board.zobrist_hash()],
+                    );
+                    (m, value)
+                })
+                .fold(None, |best: Option<(Move, f64)>, (m, value)| match best {
+                    Some((_, best_value)) if best_value >= value => best,
+                    _ => Some((m, value)),
+                })
+                .unwrap()
+        };
+
+        let mut cache = DashMap::new();
+        let cold = search(&mut cache);
+        assert!(!cache.is_empty());
+        let warm = search(&mut cache);
+        assert_eq!(warm, cold);
+        assert_eq!(cold.0, Move::Piece(F3, H4));
+        assert_eq!(search(&mut DashMap::new()), cold);
+    }
+
+    #[test]
+    fn test_centipawn_search_scores_mate() {
+        // back rank mate in one
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, G1))
+            .piece(Piece::Rook(WHITE, A1))
+            .piece(Piece::King(BLACK, G8))
+            .piece(Piece::Pawn(BLACK, F7))
+            .piece(Piece::Pawn(BLACK, G7))
+            .piece(Piece::Pawn(BLACK, H7))
+            .build();
+        let (m, _, score) = board.get_best_next_move_cp(1, None);
+        assert_eq!(m, Move::Piece(A1, A8));
+        assert_eq!(score, MATE_SCORE_CP - 1);
+    }
+
+    #[test]
+    fn test_minimax_mate_at_leaf() {
+        // back rank mate in one
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, G1))
+            .piece(Piece::Rook(WHITE, A1))
+            .piece(Piece::King(BLACK, G8))
+            .piece(Piece::Pawn(BLACK, F7))
+            .piece(Piece::Pawn(BLACK, G7))
+            .piece(Piece::Pawn(BLACK, H7))
+            .build();
+        // the mated position is a leaf of the search
+        let (m, _, value) = board.get_best_next_move(0, None);
+        assert_eq!(m, Move::Piece(A1, A8));
+        assert_eq!(value, MATE_VALUE);
+        // with a ply to spare, the mate is found sooner and rated higher
+        let (m, _, value) = board.get_best_next_move(1, None);
+        assert_eq!(m, Move::Piece(A1, A8));
+        assert_eq!(value, MATE_VALUE + 1.0);
+
+        let mated = board.apply_eval_move(Move::Piece(A1, A8));
+        let leaf = |board: Board, color| {
+            board.minimax(0, -1000000.0, 1000000.0, true, color, &mut 0, None, &mut DashMap::new(), &mut vec![])
+        };
+        assert_eq!(leaf(mated, BLACK), -MATE_VALUE);
+        assert_eq!(leaf(mated, WHITE), MATE_VALUE);
+        let stalemated = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(leaf(stalemated, WHITE), 0.0);
+    }
+
+    fn bad_captures_position() -> Board {
+        // every capture the queen can make is defended by a pawn
+        BoardBuilder::default()
+            .piece(Piece::King(WHITE, G1))
+            .piece(Piece::Queen(WHITE, D4))
+            .piece(Piece::King(BLACK, H8))
+            .piece(Piece::Pawn(BLACK, A7))
+            .piece(Piece::Pawn(BLACK, C7))
+            .piece(Piece::Pawn(BLACK, B6))
+            .piece(Piece::Pawn(BLACK, C6))
+            .piece(Piece::Pawn(BLACK, D5))
+            .piece(Piece::Pawn(BLACK, E6))
+            .piece(Piece::Pawn(BLACK, F6))
+            .piece(Piece::Pawn(BLACK, G7))
+            .piece(Piece::Pawn(BLACK, B5))
+            .piece(Piece::Pawn(BLACK, A4))
+            .piece(Piece::Pawn(BLACK, G5))
+            .piece(Piece::Pawn(BLACK, H4))
+            .build()
+    }
+
+    #[test]
+    fn test_see() {
+        let board = bad_captures_position();
+        assert_eq!(board.get_capture_moves().len(), 5);
+        assert_eq!(board.see(Move::Piece(D4, D5)), 100 - 900);
+        assert_eq!(board.see(Move::Piece(D4, A4)), 100 - 900);
+
+        let board = hanging_piece_positions()[0];
+        assert_eq!(board.see(Move::Piece(D1, D6)), 500);
+        // quiet moves exchange nothing, even onto an attacked square
+        assert_eq!(board.see(Move::Piece(D1, D2)), 0);
+        assert_eq!(board.see(Move::Piece(D1, E2)), 0);
+    }
+
+    #[test]
+    fn test_piece_values() {
+        // the knight takes the bishop and the pawn takes back
+        let board = Board::from_fen("4k3/8/3p4/4b3/8/5N2/8/4K3 w - - 0 1").unwrap();
+        let kaufman_bishop = PieceValues {
+            bishop: 3.25,
+            ..PieceValues::default()
+        };
+
+        assert_eq!(board.naive_value_for(WHITE), -1.0);
+        assert_eq!(board.naive_value_with(WHITE, &PieceValues::default()), -1.0);
+        assert_eq!(board.naive_value_with(WHITE, &kaufman_bishop), -1.25);
+        assert_eq!(board.naive_value_with(BLACK, &kaufman_bishop), 1.25);
+
+        assert_eq!(board.see(Move::Piece(F3, E5)), 0);
+        assert_eq!(board.see_with(Move::Piece(F3, E5), &kaufman_bishop), 25);
+    }
+
+    #[test]
+    fn test_quiescence_pruning() {
+        let board = bad_captures_position();
+        let search = |config: QuiescenceConfig| {
+            let mut board_count = 0;
+            let value =
+                board.quiescence_cp(-MATE_SCORE_CP, MATE_SCORE_CP, &mut board_count, None, &config);
+            (value, board_count)
+        };
+
+        let (full_value, full_count) = search(QuiescenceConfig {
+            see_pruning: false,
+            delta_margin: None,
+            ..QuiescenceConfig::default()
+        });
+        let (pruned_value, pruned_count) = search(QuiescenceConfig::default());
+        assert_eq!(pruned_value, full_value);
+        assert_eq!(pruned_value, board.static_eval_cp(WHITE, None));
+        assert_eq!(pruned_count, 1);
+        assert!(full_count > 10, "{}", full_count);
+    }
+
+    #[test]
+    fn test_quiescence_max_depth() {
+        // rooks and queens stacked on both sides of the d-file, so every
+        // capture on d5 can be answered by another one
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, H1))
+            .piece(Piece::Rook(WHITE, D1))
+            .piece(Piece::Queen(WHITE, D2))
+            .piece(Piece::Rook(WHITE, D3))
+            .piece(Piece::Knight(WHITE, C3))
+            .piece(Piece::King(BLACK, H8))
+            .piece(Piece::Rook(BLACK, D8))
+            .piece(Piece::Queen(BLACK, D7))
+            .piece(Piece::Rook(BLACK, D6))
+            .piece(Piece::Knight(BLACK, F6))
+            .piece(Piece::Pawn(BLACK, D5))
+            .build();
+        let search = |max_qdepth| {
+            let config = QuiescenceConfig {
+                see_pruning: false,
+                delta_margin: None,
+                max_qdepth,
+            };
+            let mut board_count = 0;
+            let value =
+                board.quiescence_cp(-MATE_SCORE_CP, MATE_SCORE_CP, &mut board_count, None, &config);
+            (value, board_count)
+        };
+
+        // no captures at all is just the static evaluation
+        assert_eq!(search(0), (board.static_eval_cp(WHITE, None), 1));
+        let captures = board.get_capture_moves().len() as u64;
+        assert_eq!(search(1).1, 1 + captures);
+
+        let counts: Vec<u64> = (0..8).map(|depth| search(depth).1).collect();
+        for pair in counts.windows(2) {
+            assert!(pair[0] <= pair[1], "{:?}", counts);
+        }
+        assert!(counts[2] < counts[7], "{:?}", counts);
+
+        // a single ply is enough to take a hanging queen
+        let hanging = BoardBuilder::default()
+            .piece(Piece::King(WHITE, H1))
+            .piece(Piece::Rook(WHITE, D1))
+            .piece(Piece::King(BLACK, H8))
+            .piece(Piece::Queen(BLACK, D7))
+            .build();
+        let config = QuiescenceConfig {
+            max_qdepth: 1,
+            ..QuiescenceConfig::default()
+        };
+        let value = hanging.quiescence_cp(-MATE_SCORE_CP, MATE_SCORE_CP, &mut 0, None, &config);
+        let after = hanging.apply_eval_move(Move::Piece(D1, D7));
+        assert_eq!(value, after.static_eval_cp(WHITE, None));
+    }
+
+    #[test]
+    fn test_search_mate_score() {
+        // back rank mate in one
+        let board = BoardBuilder::default()
+            .piece(Piece::King(WHITE, G1))
+            .piece(Piece::Rook(WHITE, A1))
+            .piece(Piece::King(BLACK, G8))
+            .piece(Piece::Pawn(BLACK, F7))
+            .piece(Piece::Pawn(BLACK, G7))
+            .piece(Piece::Pawn(BLACK, H7))
+            .build();
+        let result = board.search(1, None);
+        assert_eq!(result.best_move, Move::Piece(A1, A8));
+        assert_eq!(result.score, Score::Mate(1));
+
+        let result = Board::default().search(1, None);
+        assert!(matches!(result.score, Score::Cp(_)));
+
+        assert_eq!(Score::from_cp(MATE_SCORE_CP - 3), Score::Mate(2));
+        assert_eq!(Score::from_cp(-(MATE_SCORE_CP - 2)), Score::Mate(-1));
+        assert_eq!(Score::from_cp(-(MATE_SCORE_CP - 4)), Score::Mate(-2));
+        assert_eq!(Score::from_cp(-350), Score::Cp(-350));
+    }
+
+    #[test]
+    fn test_search_without_legal_moves() {
+        // fool's mate
+        let mated = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+            .unwrap();
+        assert!(mated.is_checkmate());
+        assert_eq!(mated.get_best_next_move(2, None), (Move::Resign, 0, -MATE_VALUE));
+        assert_eq!(mated.get_worst_next_move(2, None), (Move::Resign, 0, -MATE_VALUE));
+        assert_eq!(
+            mated.get_best_move_timed(Duration::from_millis(10), None),
+            (Move::Resign, 0, -MATE_VALUE)
+        );
+        assert_eq!(mated.get_best_next_move_cp(2, None), (Move::Resign, 0, -MATE_SCORE_CP));
+        assert_eq!(mated.search(2, None).score, Score::Mate(0));
+
+        let stalemated = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(stalemated.is_stalemate());
+        assert_eq!(stalemated.get_best_next_move(2, None), (Move::Resign, 0, 0.0));
+        assert_eq!(stalemated.get_best_next_move_cp(2, None), (Move::Resign, 0, 0));
+    }
+
+    #[test]
+    fn test_search_with_one_legal_move() {
+        // the king is in check, and taking the rook is the only way out
+        let board = Board::from_fen("1krK2n1/3P4/8/8/R7/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(board.get_legal_moves(), vec![Move::Piece(D7, C8)]);
+        let (m, _, score) = board.get_best_next_move_cp(2, None);
+        assert_eq!((m, score), (Move::Piece(D7, C8), MATE_SCORE_CP - 1));
+        assert_eq!(board.search(2, None).score, Score::Mate(1));
     }
 }
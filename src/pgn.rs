@@ -0,0 +1,342 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{Board, Color, GameResult, Move, Outcome, PositionHistory};
+
+/// PGN lines are kept to this many characters, as the export format asks.
+const MAX_LINE_LEN: usize = 80;
+
+/// A game recorded for export in Portable Game Notation.
+///
+/// Each move is recorded with the board it was played from, so the moves
+/// can be written in standard algebraic notation and numbered from the
+/// position the game started in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    event: String,
+    white: String,
+    black: String,
+    outcome: Outcome,
+    moves: Vec<(Board, Move)>,
+}
+
+impl Default for PgnGame {
+    /// A game with unknown event and players, and no moves yet. Unknown
+    /// tags are written `?`, as PGN does.
+    fn default() -> Self {
+        Self {
+            event: String::from("?"),
+            white: String::from("?"),
+            black: String::from("?"),
+            outcome: Outcome::Unfinished,
+            moves: Vec::new(),
+        }
+    }
+}
+
+impl PgnGame {
+    /// Set the name of the event the game was played in.
+    pub fn event(mut self, event: &str) -> Self {
+        self.event = String::from(event);
+        self
+    }
+
+    /// Set the name of the player with the white pieces.
+    pub fn white(mut self, white: &str) -> Self {
+        self.white = String::from(white);
+        self
+    }
+
+    /// Set the name of the player with the black pieces.
+    pub fn black(mut self, black: &str) -> Self {
+        self.black = String::from(black);
+        self
+    }
+
+    /// Record a move, along with the board it was played from.
+    pub fn record(&mut self, board: Board, m: Move) {
+        self.moves.push((board, m));
+    }
+
+    /// Set how the game ended. A game without an outcome is written as
+    /// still in progress.
+    pub fn set_outcome(&mut self, outcome: Outcome) {
+        self.outcome = outcome;
+    }
+
+    /// Get the moves recorded so far, each with the board it was played from.
+    pub fn moves(&self) -> &[(Board, Move)] {
+        &self.moves
+    }
+
+    /// Write the game in Portable Game Notation: the Event, White, Black and
+    /// Result tags, then the numbered moves in standard algebraic notation
+    /// and the result token.
+    ///
+    /// A game that doesn't start from the starting position also gets SetUp
+    /// and FEN tags. Resignations aren't written as moves, since the result
+    /// token already says who won.
+    pub fn to_pgn(&self) -> String {
+        let result = match self.outcome {
+            Outcome::Victory(Color::White) => "1-0",
+            Outcome::Victory(Color::Black) => "0-1",
+            Outcome::Draw => "1/2-1/2",
+            Outcome::Unfinished => "*",
+        };
+
+        let mut tags = vec![
+            ("Event", self.event.clone()),
+            ("White", self.white.clone()),
+            ("Black", self.black.clone()),
+            ("Result", String::from(result)),
+        ];
+        if let Some((start, _)) = self.moves.first() {
+            if start.fen() != Board::default().fen() {
+                tags.push(("SetUp", String::from("1")));
+                tags.push(("FEN", start.fen()));
+            }
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in tags {
+            pgn += &format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""));
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        for (i, (board, m)) in self.moves.iter().enumerate() {
+            if *m == Move::Resign {
+                continue;
+            }
+            match board.get_turn_color() {
+                Color::White => tokens.push(format!("{}.", board.fullmove_number())),
+                // black's move only needs a number when it starts the movetext
+                Color::Black if i == 0 => tokens.push(format!("{}...", board.fullmove_number())),
+                Color::Black => {}
+            }
+            tokens.push(board.move_to_san(*m));
+        }
+        tokens.push(result.to_string());
+
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > MAX_LINE_LEN {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            pgn += &token;
+        }
+        pgn.push('\n');
+        pgn
+    }
+}
+
+/// Replay the moves of a game in PGN from the starting position, getting
+/// each move along with the board it was played from.
+///
+/// The moves are read in standard algebraic notation. Tag pairs, move
+/// numbers, comments, variations, NAGs like `$1` and result tokens are
+/// skipped. An unreadable or illegal move, or a move after the game has
+/// ended, is an error naming the move and its index among the moves,
+/// counting from 0.
+pub fn from_pgn_moves(pgn: &str) -> Result<Vec<(Board, Move)>, String> {
+    let mut result = Vec::new();
+    let mut board = Board::default();
+    let mut history = PositionHistory::default();
+    let mut game_over = false;
+
+    for san in movetext_tokens(pgn) {
+        let index = result.len();
+        if game_over {
+            return Err(format!("move {} `{}` is played after the game ended", index, san));
+        }
+        let m = Move::from_san(&san, &board).map_err(|e| format!("move {}: {}", index, e))?;
+        result.push((board, m));
+        match board.play_move_with(m, &mut history) {
+            GameResult::Continuing(next) => board = next,
+            GameResult::IllegalMove(_) => return Err(format!("move {}: `{}` is not a legal move", index, san)),
+            GameResult::Victory(_) | GameResult::Stalemate => game_over = true,
+        }
+    }
+    Ok(result)
+}
+
+/// Get the moves written in PGN, leaving out everything else.
+fn movetext_tokens(pgn: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    // how deep inside variations the reader is
+    let mut variation_depth = 0;
+    let mut chars = pgn.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if !c.is_whitespace() && !"{[;()".contains(c) {
+            token.push(c);
+            // a move number can be written right against its move, like `1.e4`
+            if c == '.' && chars.peek() != Some(&'.') {
+                token.clear();
+            }
+            continue;
+        }
+
+        if variation_depth == 0 && is_move(&token) {
+            tokens.push(token.clone());
+        }
+        token.clear();
+        match c {
+            // comments and tag pairs run to their closing bracket
+            '{' | '[' => {
+                let close = if c == '{' { '}' } else { ']' };
+                for c in chars.by_ref() {
+                    if c == close {
+                        break;
+                    }
+                }
+            }
+            // as do comments that start with a semicolon, at the end of the line
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ => {}
+        }
+    }
+    if variation_depth == 0 && is_move(&token) {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Is a token from the movetext a move, rather than a NAG or a result?
+fn is_move(token: &str) -> bool {
+    !token.is_empty() && !token.starts_with('$') && !matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    fn scholars_mate() -> PgnGame {
+        let mut pgn = PgnGame::default().event("Thunderdome").white("material").black("random");
+        let mut board = Board::default();
+        for san in &["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"] {
+            let m = Move::from_san(san, &board).unwrap();
+            pgn.record(board, m);
+            match board.play_move(m) {
+                GameResult::Continuing(next) => board = next,
+                GameResult::Victory(winner) => pgn.set_outcome(Outcome::Victory(winner)),
+                other => panic!("{} gave {:?}", san, other),
+            }
+        }
+        pgn
+    }
+
+    #[test]
+    fn test_to_pgn() {
+        let pgn = scholars_mate().to_pgn();
+        assert_eq!(
+            pgn,
+            "[Event \"Thunderdome\"]\n\
+             [White \"material\"]\n\
+             [Black \"random\"]\n\
+             [Result \"1-0\"]\n\
+             \n\
+             1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"
+        );
+
+        // the movetext reads back move by move, each number where it belongs
+        let movetext = pgn.lines().last().unwrap();
+        let mut board = Board::default();
+        let mut result = None;
+        for token in movetext.split_whitespace() {
+            if token.ends_with('.') {
+                assert_eq!(token, format!("{}.", board.fullmove_number()));
+                assert_eq!(board.get_turn_color(), WHITE);
+            } else if token == "1-0" {
+                assert_eq!(result, Some(GameResult::Victory(WHITE)));
+            } else {
+                let next = board.play_move(Move::from_san(token, &board).unwrap());
+                if let GameResult::Continuing(next) = next {
+                    board = next;
+                } else {
+                    result = Some(next);
+                }
+            }
+        }
+        assert_eq!(board.fullmove_number(), 4);
+    }
+
+    #[test]
+    fn test_from_pgn_moves() {
+        let pgn = "[Event \"Casual game\"]\n\n\
+                   1. e4 e5 2. Nf3 Nc6 3.Bb5 a6 {the Morphy defence} 4. Ba4 (4. Bxc6 dxc6 (4... bxc6) 5. O-O) \
+                   4... Nf6 $1 5. O-O Be7 ; the main line\n1/2-1/2";
+        let moves = from_pgn_moves(pgn).unwrap();
+        assert_eq!(moves.len(), 10);
+        assert_eq!(moves[0], (Board::default(), Move::Piece(E2, E4)));
+        assert_eq!(moves[8].1, Move::KingSideCastle);
+
+        let (last_board, last_move) = moves[9];
+        let final_board = last_board.apply_eval_move(last_move);
+        assert_eq!(final_board.fen(), "r1bqk2r/1pppbppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 4 6");
+
+        // exported games read back
+        let game = scholars_mate();
+        assert_eq!(from_pgn_moves(&game.to_pgn()).unwrap(), game.moves());
+    }
+
+    #[test]
+    fn test_from_pgn_moves_errors() {
+        assert_eq!(from_pgn_moves("").unwrap(), vec![]);
+        assert_eq!(
+            from_pgn_moves("1. e4 e5 2. Ke3"),
+            Err(String::from("move 2: `Ke3` is not a legal move"))
+        );
+        assert!(from_pgn_moves("1. e4 e5 2. Zz9").unwrap_err().starts_with("move 2:"));
+        assert_eq!(
+            from_pgn_moves("1. f3 e5 2. g4 Qh4# 3. a3"),
+            Err(String::from("move 4 `a3` is played after the game ended"))
+        );
+    }
+
+    #[test]
+    fn test_to_pgn_from_position() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 40").unwrap();
+        let mut pgn = PgnGame::default().white("a \"quoted\" name");
+        for m in &[Move::Piece(E8, D7), Move::Piece(E2, E4), Move::Resign] {
+            pgn.record(board, *m);
+            board = board.apply_eval_move(*m);
+        }
+        pgn.set_outcome(Outcome::Victory(WHITE));
+
+        let pgn = pgn.to_pgn();
+        assert!(pgn.contains("[White \"a \\\"quoted\\\" name\"]\n"));
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 40\"]\n"));
+        assert!(pgn.ends_with("\n\n40... Kd7 41. e4 1-0\n"));
+
+        // long games are wrapped
+        let mut pgn = PgnGame::default();
+        let mut board = Board::default();
+        for _ in 0..20 {
+            for m in &[Move::Piece(G1, F3), Move::Piece(G8, F6), Move::Piece(F3, G1), Move::Piece(F6, G8)] {
+                pgn.record(board, *m);
+                board = board.apply_eval_move(*m);
+            }
+        }
+        let pgn = pgn.to_pgn();
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LEN));
+        assert!(pgn.ends_with(" *\n"));
+    }
+}
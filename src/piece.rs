@@ -1,6 +1,7 @@
 use super::{Board, Color, Move, Position};
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::cmp::Ordering;
 use core::convert::TryFrom;
 
 /// A piece on a board.
@@ -22,6 +23,86 @@ pub enum Piece {
     Pawn(Color, Position),
 }
 
+/// The material value of each kind of piece, in pawns.
+///
+/// These are the values the material heuristic and static exchange
+/// evaluation count with. The default is the conventional 1/3/3/5/9.
+/// Kings can't be traded, so they aren't configurable.
+///
+/// Values are compared with `f64::total_cmp`, so that a `Board` counting
+/// with them can still be compared and ordered.
+#[derive(Clone, Copy, Debug)]
+pub struct PieceValues {
+    pub pawn: f64,
+    pub knight: f64,
+    pub bishop: f64,
+    pub rook: f64,
+    pub queen: f64,
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        Self {
+            pawn: 1.0,
+            knight: 3.0,
+            bishop: 3.0,
+            rook: 5.0,
+            queen: 9.0,
+        }
+    }
+}
+
+impl PartialEq for PieceValues {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PieceValues {}
+
+impl PartialOrd for PieceValues {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PieceValues {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.fields()
+            .iter()
+            .zip(other.fields().iter())
+            .map(|(a, b)| a.total_cmp(b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PieceValues {
+    fn fields(&self) -> [f64; 5] {
+        [self.pawn, self.knight, self.bishop, self.rook, self.queen]
+    }
+
+    /// Get the value of a piece in pawns.
+    ///
+    /// A king is worth 99999, so it is never worth trading for anything.
+    /// `Piece::get_material_value` reads the default values from here.
+    pub fn value(&self, piece: Piece) -> f64 {
+        match piece {
+            Piece::King(..) => 99999.0,
+            Piece::Queen(..) => self.queen,
+            Piece::Rook(..) => self.rook,
+            Piece::Bishop(..) => self.bishop,
+            Piece::Knight(..) => self.knight,
+            Piece::Pawn(..) => self.pawn,
+        }
+    }
+
+    /// Get the value of a piece in centipawns.
+    pub fn value_cp(&self, piece: Piece) -> i32 {
+        (self.value(piece) * 100.0).round() as i32
+    }
+}
+
 const WHITE_KING_POSITION_WEIGHTS: [[f64; 8]; 8] = [
     [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
     [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
@@ -238,16 +319,11 @@ impl Piece {
     /// | Bishop | 3 |
     /// | Knight | 3 |
     /// | Pawn | 1 |
+    ///
+    /// These are the default `PieceValues`, rounded to whole pawns.
     #[inline]
     pub fn get_material_value(&self) -> i32 {
-        match self {
-            Self::King(_, _) => 99999,
-            Self::Queen(_, _) => 9,
-            Self::Rook(_, _) => 5,
-            Self::Bishop(_, _) => 3,
-            Self::Knight(_, _) => 3,
-            Self::Pawn(_, _) => 1,
-        }
+        PieceValues::default().value(*self).round() as i32
     }
 
     /// Get the weighted value of a piece. This simply factors in position
@@ -260,6 +336,13 @@ impl Piece {
     /// its material value, plus or minus a weight ranging between 5.0 and -5.0.
     #[inline]
     pub fn get_weighted_value(&self) -> f64 {
+        self.get_position_weight() + (self.get_material_value() * 10) as f64
+    }
+
+    /// Get the weight the piece tables give a piece for where it stands,
+    /// without its material value.
+    #[inline]
+    pub fn get_position_weight(&self) -> f64 {
         let weights = match self {
             Self::King(c, _) => match c {
                 Color::White => WHITE_KING_POSITION_WEIGHTS,
@@ -287,7 +370,6 @@ impl Piece {
             },
         };
         weights[(7 - self.get_pos().get_row()) as usize][self.get_pos().get_col() as usize]
-            + (self.get_material_value() * 10) as f64
     }
 
     /// Get the color of a given piece.
@@ -440,6 +522,19 @@ impl Piece {
     /// This is used for move generation.
     #[inline]
     pub(crate) fn get_legal_moves(&self, board: &Board) -> Vec<Move> {
+        let color = self.get_color();
+        self.get_pseudo_legal_moves(board)
+            .into_iter()
+            .filter(|x| board.is_legal_move(*x, color))
+            .collect::<Vec<Move>>()
+    }
+
+    /// Get the moves a given piece could make if leaving its own king
+    /// in check were allowed.
+    ///
+    /// This is the first stage of move generation, before moves are checked
+    /// against the rest of the board by `get_legal_moves`.
+    pub(crate) fn get_pseudo_legal_moves(&self, board: &Board) -> Vec<Move> {
         let mut result = Vec::new();
         match *self {
             Self::Pawn(ally_color, pos) => {
@@ -492,7 +587,8 @@ impl Piece {
                 }
                 if board.can_kingside_castle(ally_color) {
                     result.push(Move::KingSideCastle);
-                } else if board.can_queenside_castle(ally_color) {
+                }
+                if board.can_queenside_castle(ally_color) {
                     result.push(Move::QueenSideCastle);
                 }
             }
@@ -583,18 +679,19 @@ impl Piece {
             }
         }
 
-        let color = self.get_color();
         result
             .into_iter()
             .filter(|x| match x {
                 Move::Piece(from, to) => {
-                    if from.is_on_board() && to.is_on_board() {
-                        board.is_legal_move(*x, color)
-                    } else {
-                        false
-                    }
+                    from.is_on_board()
+                        && to.is_on_board()
+                        && (self.is_legal_move(*to, board)
+                            || self.is_pawn()
+                                && board.get_en_passant() == Some(*to)
+                                && from.get_col() != to.get_col())
                 }
-                _ => board.is_legal_move(*x, color),
+                // castles are only generated when they are legal
+                _ => true,
             })
             .collect::<Vec<Move>>()
     }
@@ -692,7 +789,16 @@ impl Piece {
     /// Verify that attacking a given square is a legal move.
     #[inline]
     pub(crate) fn is_legal_attack(&self, new_pos: Position, board: &Board) -> bool {
-        if board.has_ally_piece(new_pos, self.get_color()) || new_pos.is_off_board() {
+        !board.has_ally_piece(new_pos, self.get_color()) && self.attacks(new_pos, board)
+    }
+
+    /// Does this piece attack a given square?
+    ///
+    /// Unlike `is_legal_attack`, this ignores whatever stands on the
+    /// square, so a piece also attacks the squares of the allies it defends.
+    #[inline]
+    pub(crate) fn attacks(&self, new_pos: Position, board: &Board) -> bool {
+        if new_pos.is_off_board() || new_pos == self.get_pos() {
             return false;
         }
 
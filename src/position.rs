@@ -103,6 +103,35 @@ impl core::fmt::Display for Position {
     }
 }
 
+/// Why a string couldn't be parsed as a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionError {
+    /// The string isn't exactly two characters long.
+    WrongLength,
+    /// The first character isn't a file from `a` to `h`.
+    BadFile(char),
+    /// The second character isn't a rank from `1` to `8`.
+    BadRank(char),
+}
+
+impl core::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::WrongLength => write!(f, "a position must be a file and a rank, like `e4`"),
+            Self::BadFile(c) => write!(f, "invalid column character `{}`", c),
+            Self::BadRank(c) => write!(f, "invalid row character `{}`", c),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+impl From<PositionError> for String {
+    fn from(error: PositionError) -> Self {
+        error.to_string()
+    }
+}
+
 impl Position {
     /// Return the starting position for a given color's king.
     #[inline]
@@ -137,16 +166,21 @@ impl Position {
 
     /// Parse a position from PGN. This simply just supports positions like
     /// `e4` and `D8`.
+    ///
+    /// This is `parse_pgn` with the error turned into a message.
     pub fn pgn(s: &str) -> Result<Self, String> {
+        Self::parse_pgn(s).map_err(String::from)
+    }
+
+    /// Parse a position from PGN, like `pgn`, saying what was wrong with
+    /// the input if it isn't a square.
+    pub fn parse_pgn(s: &str) -> Result<Self, PositionError> {
         let s = s.trim().to_lowercase();
-        let col = s.chars().next().ok_or(format!("invalid pgn `{}`", s))?;
-        let row = s
-            .chars()
-            .nth(1)
-            .ok_or(format!("invalid pgn `{}`", s))?
-            .to_string()
-            .parse::<u32>()
-            .map_err(|_| format!("invalid pgn `{}`", s))? as i32;
+        let mut chars = s.chars();
+        let (col, row) = match (chars.next(), chars.next(), chars.next()) {
+            (Some(col), Some(row), None) => (col, row),
+            _ => return Err(PositionError::WrongLength),
+        };
         let c = match col {
             'a' => 0,
             'b' => 1,
@@ -156,13 +190,12 @@ impl Position {
             'f' => 5,
             'g' => 6,
             'h' => 7,
-            _ => return Err(format!("invalid column character `{}`", col)),
+            _ => return Err(PositionError::BadFile(col)),
         };
 
-        if 1 <= row || row <= 8 {
-            Ok(Self::new(row - 1, c))
-        } else {
-            Err(format!("invalid row number `{}`", row))
+        match row.to_digit(10) {
+            Some(r) if (1..=8).contains(&r) => Ok(Self::new(r as i32 - 1, c)),
+            _ => Err(PositionError::BadRank(row)),
         }
     }
 
@@ -178,6 +211,19 @@ impl Position {
         self.row < 0 || self.row > 7 || self.col < 0 || self.col > 7
     }
 
+    /// Get the color of the square at this position on the board.
+    ///
+    /// Light squares are `Color::White` and dark squares are `Color::Black`,
+    /// so A1 is dark and H1 is light.
+    #[inline]
+    pub fn square_color(&self) -> Color {
+        if (self.row + self.col) % 2 == 0 {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
     /// Get the row number of the position.
     /// This can be any of 0, 1, 2, 3, 4, 5, 6, or 7.
     #[inline]
@@ -446,3 +492,49 @@ impl Position {
             || (self.row - other.row).abs() == 1 && (self.col - other.col).abs() == 2
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pgn() {
+        assert_eq!(Position::parse_pgn("e4"), Ok(E4));
+        assert_eq!(Position::parse_pgn(" D8 "), Ok(D8));
+        assert_eq!(Position::parse_pgn("h1"), Ok(H1));
+
+        assert_eq!(Position::parse_pgn("e9"), Err(PositionError::BadRank('9')));
+        assert_eq!(Position::parse_pgn("e0"), Err(PositionError::BadRank('0')));
+        assert_eq!(Position::parse_pgn("ex"), Err(PositionError::BadRank('x')));
+        assert_eq!(Position::parse_pgn("i2"), Err(PositionError::BadFile('i')));
+        assert_eq!(Position::parse_pgn("e"), Err(PositionError::WrongLength));
+        assert_eq!(Position::parse_pgn(""), Err(PositionError::WrongLength));
+        assert_eq!(Position::parse_pgn("abc"), Err(PositionError::WrongLength));
+        assert_eq!(Position::parse_pgn("e10"), Err(PositionError::WrongLength));
+
+        // the old API keeps returning a message
+        assert_eq!(Position::pgn("e4"), Ok(E4));
+        assert_eq!(Position::pgn("i2"), Err(String::from("invalid column character `i`")));
+        assert!(Position::pgn("e9").is_err());
+    }
+
+    #[test]
+    fn test_square_color() {
+        for pos in &[A1, C1, B2, H2, D4, E5, A7, H8, F8] {
+            assert_eq!(pos.square_color(), Color::Black, "{}", pos);
+        }
+        for pos in &[H1, B1, A2, E4, D5, B7, G8, A8] {
+            assert_eq!(pos.square_color(), Color::White, "{}", pos);
+        }
+
+        // squares alternate along every rank and file
+        for row in 0..8 {
+            for col in 0..7 {
+                let pos = Position::new(row, col);
+                assert_ne!(pos.square_color(), pos.next_right().square_color());
+                let pos = Position::new(col, row);
+                assert_ne!(pos.square_color(), pos.next_above().square_color());
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{Color, GameResult};
+
+/// The K-factor Thunderdome rates its engines with: the most a rating can
+/// move after a single game.
+pub const DEFAULT_K_FACTOR: f64 = 32.0;
+
+/// The score a player rated `elo` is expected to make against a player
+/// rated `opponent_elo`, from 0.0 to 1.0.
+pub fn expected_score(elo: f64, opponent_elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent_elo - elo) / 400.0))
+}
+
+/// Update the ELO ratings of White and Black after a game, given the result
+/// of the game and the K-factor to rate with.
+///
+/// The ratings are always White's first, whoever won. A draw scores half a
+/// point each, and a `Continuing` or `IllegalMove` result leaves both
+/// ratings as they were.
+pub fn update_elo(white_elo: f64, black_elo: f64, result: GameResult, k_factor: f64) -> (f64, f64) {
+    let white_score = match result {
+        GameResult::Victory(Color::White) => 1.0,
+        GameResult::Victory(Color::Black) => 0.0,
+        GameResult::Stalemate => 0.5,
+        GameResult::Continuing(_) | GameResult::IllegalMove(_) => return (white_elo, black_elo),
+    };
+    update_elo_with_score(white_elo, black_elo, white_score, k_factor)
+}
+
+/// Update the ELO ratings of two players after a game, given what player 1
+/// scored in it: 1.0 for a win, 0.0 for a loss, and the draw score (normally
+/// 0.5) for a draw.
+pub fn update_elo_with_score(player1_elo: f64, player2_elo: f64, player1_score: f64, k_factor: f64) -> (f64, f64) {
+    let expected = expected_score(player1_elo, player2_elo);
+    (
+        player1_elo + k_factor * (player1_score - expected),
+        player2_elo + k_factor * (expected - player1_score),
+    )
+}
+
+/// Get White's score for a draw in which `penalized`, if anyone, scores
+/// `draw_score` and their opponent scores the rest of the point.
+///
+/// With nobody penalized a draw is half a point each whatever `draw_score`
+/// is, so a draw score other than 0.5 never moves rating from one color to
+/// the other on its own.
+pub fn draw_score_for_white(penalized: Option<Color>, draw_score: f64) -> f64 {
+    match penalized {
+        Some(Color::White) => draw_score,
+        Some(Color::Black) => 1.0 - draw_score,
+        None => 0.5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Board;
+
+    fn assert_elos(actual: (f64, f64), expected: (f64, f64)) {
+        assert!((actual.0 - expected.0).abs() < 0.01, "{:?} != {:?}", actual, expected);
+        assert!((actual.1 - expected.1).abs() < 0.01, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_update_elo() {
+        let k = DEFAULT_K_FACTOR;
+        assert_elos(update_elo(1500.0, 1500.0, GameResult::Victory(Color::White), k), (1516.0, 1484.0));
+        assert_elos(update_elo(1500.0, 1500.0, GameResult::Victory(Color::Black), k), (1484.0, 1516.0));
+        assert_elos(update_elo(1500.0, 1500.0, GameResult::Stalemate, k), (1500.0, 1500.0));
+        assert_elos(update_elo(1500.0, 1500.0, GameResult::Victory(Color::White), 16.0), (1508.0, 1492.0));
+
+        // the favorite gains little for a win, and loses more for a draw
+        assert_elos(update_elo(1600.0, 1400.0, GameResult::Victory(Color::White), k), (1607.69, 1392.31));
+        assert_elos(update_elo(1600.0, 1400.0, GameResult::Stalemate, k), (1591.69, 1408.31));
+        assert_elos(update_elo(1400.0, 1600.0, GameResult::Victory(Color::Black), k), (1392.31, 1607.69));
+
+        // an upset over a much stronger player is worth nearly the whole K-factor
+        assert_elos(update_elo(2400.0, 1600.0, GameResult::Victory(Color::Black), k), (2368.32, 1631.68));
+        assert!(expected_score(2400.0, 1600.0) > 0.99);
+
+        let unfinished = GameResult::Continuing(Board::default());
+        assert_eq!(update_elo(1600.0, 1400.0, unfinished, k), (1600.0, 1400.0));
+        assert_eq!(update_elo(1600.0, 1400.0, GameResult::IllegalMove(crate::Move::Resign), k), (1600.0, 1400.0));
+    }
+
+    #[test]
+    fn test_update_elo_with_score() {
+        assert_elos(update_elo_with_score(1500.0, 1500.0, 1.0, 32.0), (1516.0, 1484.0));
+        assert_elos(update_elo_with_score(1500.0, 1500.0, 0.0, 32.0), (1484.0, 1516.0));
+
+        // a lower draw score costs player 1 rating
+        assert_elos(update_elo_with_score(1500.0, 1500.0, 0.4, 32.0), (1496.8, 1503.2));
+    }
+
+    #[test]
+    fn test_draw_score_for_white() {
+        assert_eq!(draw_score_for_white(None, 0.4), 0.5);
+        assert_eq!(draw_score_for_white(Some(Color::White), 0.4), 0.4);
+        assert_eq!(draw_score_for_white(Some(Color::Black), 0.4), 0.6);
+
+        // only the penalized player loses rating, whichever color they are
+        let white = update_elo_with_score(1500.0, 1500.0, draw_score_for_white(Some(Color::White), 0.4), 32.0);
+        let black = update_elo_with_score(1500.0, 1500.0, draw_score_for_white(Some(Color::Black), 0.4), 32.0);
+        assert_elos(white, (1496.8, 1503.2));
+        assert_elos(black, (1503.2, 1496.8));
+        assert_elos(update_elo_with_score(1500.0, 1500.0, draw_score_for_white(None, 0.4), 32.0), (1500.0, 1500.0));
+    }
+}
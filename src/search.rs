@@ -0,0 +1,246 @@
+use core::time::Duration;
+
+use crate::{Move, MATE_SCORE_CP};
+
+/// Settings for the capture-only search that settles a position before
+/// it is statically evaluated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuiescenceConfig {
+    /// Skip captures that lose material according to static exchange
+    /// evaluation.
+    pub see_pruning: bool,
+    /// Skip captures that can't raise alpha even after winning the captured
+    /// piece outright and gaining this many more centipawns.
+    /// `None` turns delta pruning off.
+    pub delta_margin: Option<i32>,
+    /// Stop capturing after this many plies and take the static evaluation,
+    /// so long capture chains can't stall the search.
+    pub max_qdepth: u32,
+}
+
+impl Default for QuiescenceConfig {
+    fn default() -> Self {
+        Self {
+            see_pruning: true,
+            delta_margin: Some(200),
+            max_qdepth: 8,
+        }
+    }
+}
+
+/// The score of a searched position, from the point of view of the
+/// player the search was run for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Score {
+    /// An ordinary evaluation in centipawns.
+    Cp(i32),
+    /// A forced mate in this many moves. A positive count means the player
+    /// delivers the mate, a negative count means the player gets mated.
+    Mate(i32),
+}
+
+/// Centipawn scores this close to `MATE_SCORE_CP` are mate scores.
+const MAX_MATE_PLY: i32 = 1000;
+
+impl Score {
+    /// Convert a centipawn score from the search, where a mate `n` plies
+    /// away scores `MATE_SCORE_CP - n`, into a `Score`.
+    pub fn from_cp(cp: i32) -> Self {
+        if cp.abs() < MATE_SCORE_CP - MAX_MATE_PLY {
+            return Score::Cp(cp);
+        }
+        let plies = MATE_SCORE_CP - cp.abs();
+        let moves = (plies + 1) / 2;
+        Score::Mate(if cp > 0 { moves } else { -moves })
+    }
+}
+
+/// The outcome of searching a position for the best move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The best move found.
+    pub best_move: Move,
+    /// The score of the best move.
+    pub score: Score,
+    /// The number of boards evaluated to come to a conclusion.
+    pub nodes: u64,
+    /// How the evaluation at the end of the principal variation splits into
+    /// material and everything else. Only `Board::analyze` fills this in, so
+    /// ordinary searches don't pay for it.
+    pub split: Option<EvalSplit>,
+}
+
+/// A static evaluation split into material and the positional remainder,
+/// both in centipawns for the player the search was run for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalSplit {
+    /// The terms of the evaluation that count material, like the value of
+    /// each piece in the piece tables.
+    pub material: i32,
+    /// Every other term of the evaluation, like where the pieces stand.
+    pub positional: i32,
+}
+
+impl EvalSplit {
+    /// The whole evaluation.
+    pub fn total(&self) -> i32 {
+        self.material + self.positional
+    }
+}
+
+/// How a score stored in a transposition table relates to the position's
+/// true score, which depends on the window it was searched with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is the position's score.
+    Exact,
+    /// The search failed high, so the position scores at least this much.
+    Lower,
+    /// The search failed low, so the position scores at most this much.
+    Upper,
+}
+
+/// What `Evaluate::pvs` remembers about a position it has searched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TtEntry {
+    /// The number of plies the position was searched to.
+    pub depth: i32,
+    /// The score for the player to move, in centipawns. Mates are counted
+    /// from this position rather than from the root of the search.
+    pub score: i32,
+    pub bound: Bound,
+    /// The best move found, which is searched first the next time.
+    pub best_move: Move,
+}
+
+/// Convert a mate score `ply` plies from the root into one counted from the
+/// position itself, so it stays right wherever the position is reached.
+pub(crate) fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_SCORE_CP - MAX_MATE_PLY {
+        score + ply
+    } else if score <= -MATE_SCORE_CP + MAX_MATE_PLY {
+        score - ply
+    } else {
+        score
+    }
+}
+
+/// Undo `score_to_tt` for a position `ply` plies from the root.
+pub(crate) fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_SCORE_CP - MAX_MATE_PLY {
+        score - ply
+    } else if score <= -MATE_SCORE_CP + MAX_MATE_PLY {
+        score + ply
+    } else {
+        score
+    }
+}
+
+/// The branching factor assumed before two depths of a search have been
+/// timed, and the most that is ever assumed.
+const DEFAULT_BRANCHING_FACTOR: f64 = 6.0;
+const MAX_BRANCHING_FACTOR: f64 = 40.0;
+
+/// Estimate how long the next depth of an iterative deepening search will
+/// take, given how long each depth so far took, shallowest first.
+///
+/// The next depth is assumed to take as much longer than the last one as
+/// the last one took compared to the one before it: the effective branching
+/// factor. With fewer than two depths timed, `DEFAULT_BRANCHING_FACTOR` is
+/// used instead.
+pub fn time_to_next_depth(iteration_times: &[Duration]) -> Duration {
+    let branching_factor = match iteration_times {
+        [] => return Duration::ZERO,
+        [.., previous, last] if !previous.is_zero() => {
+            (last.as_secs_f64() / previous.as_secs_f64()).clamp(1.0, MAX_BRANCHING_FACTOR)
+        }
+        _ => DEFAULT_BRANCHING_FACTOR,
+    };
+    iteration_times[iteration_times.len() - 1].mul_f64(branching_factor)
+}
+
+/// Should an iterative deepening search start its next depth, with
+/// `remaining` left of its budget?
+///
+/// A depth is only worth starting when `time_to_next_depth` expects it to
+/// finish in time, since a depth cut short is thrown away.
+pub fn next_depth_fits(iteration_times: &[Duration], remaining: Duration) -> bool {
+    time_to_next_depth(iteration_times) <= remaining
+}
+
+/// How much each heuristic counts towards the static evaluation, by name.
+///
+/// Engines are stored as `[f64; 6]` arrays of the first six weights, in the
+/// order of these fields, and convert to and from this struct with `From`.
+/// The weights after those are left at zero by the conversion, and dropped
+/// by the conversion back. The search and evaluation methods take anything
+/// that converts into this, so an `EvalEngine`, an array or an
+/// `Option<[f64; 6]>` all work there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvalEngine {
+    /// The piece tables, which rate each piece by where it stands.
+    pub material: f64,
+    /// How many moves each side's pieces have on average.
+    pub mobility: f64,
+    /// Plain material counted with `PieceValues`, and checks.
+    pub naive: f64,
+    /// How many squares each side controls.
+    pub control: f64,
+    /// How close the nearest enemy piece is to each king.
+    pub closest: f64,
+    /// How much material is left on the board, to favor trading down.
+    pub trade: f64,
+    /// Knights and bishops with almost no moves.
+    pub trapped_pieces: f64,
+    /// Combinations of pieces worth more or less than the pieces alone.
+    pub material_imbalance: f64,
+    /// Rooks behind passed pawns on their file.
+    pub rook_behind_passer: f64,
+    /// Developed knights and bishops, and a king off its starting square,
+    /// in the opening.
+    pub development: f64,
+}
+
+impl Default for EvalEngine {
+    /// Only the piece tables, the same as an engine of `None`.
+    fn default() -> Self {
+        Self::from([1.0, 0.0, 0.0, 0.0, 0.0, 0.0])
+    }
+}
+
+impl From<[f64; 6]> for EvalEngine {
+    fn from([material, mobility, naive, control, closest, trade]: [f64; 6]) -> Self {
+        Self {
+            material,
+            mobility,
+            naive,
+            control,
+            closest,
+            trade,
+            trapped_pieces: 0.0,
+            material_imbalance: 0.0,
+            rook_behind_passer: 0.0,
+            development: 0.0,
+        }
+    }
+}
+
+impl From<Option<[f64; 6]>> for EvalEngine {
+    /// The weights of an engine, or the default engine for `None`.
+    fn from(engine: Option<[f64; 6]>) -> Self {
+        engine.map(Self::from).unwrap_or_default()
+    }
+}
+
+impl From<EvalEngine> for [f64; 6] {
+    fn from(engine: EvalEngine) -> Self {
+        [
+            engine.material,
+            engine.mobility,
+            engine.naive,
+            engine.control,
+            engine.closest,
+            engine.trade,
+        ]
+    }
+}
@@ -0,0 +1,555 @@
+use alloc::vec::Vec;
+
+use crate::{Board, Color, Evaluate, GameResult, Move, PositionHistory};
+
+/// Settings for playing a game between two engines without a database.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchConfig {
+    /// The number of plies each engine searches before choosing a move.
+    pub depth: i32,
+    /// The game is stopped as unfinished after this many plies.
+    pub max_plies: usize,
+    /// Adjudicate an engine as resigning once it keeps blundering.
+    /// `None` lets every game play out.
+    pub blunder_resign: Option<BlunderPolicy>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            depth: 2,
+            max_plies: 200,
+            blunder_resign: None,
+        }
+    }
+}
+
+/// When to adjudicate an engine as resigning for repeatedly blundering.
+///
+/// A move is a blunder when the score the engine's own search gives it is
+/// more than `drop_cp` centipawns below the score of the engine's previous
+/// move. The engine resigns on its turn after its `max_blunders`th blunder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlunderPolicy {
+    pub drop_cp: i32,
+    pub max_blunders: usize,
+}
+
+/// How a game between two engines ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// One side checkmated the other, or the other side resigned.
+    Victory(Color),
+    /// The game was drawn.
+    Draw,
+    /// The ply limit was reached before the game ended.
+    Unfinished,
+}
+
+/// A full record of a game played between two engines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    /// The position the game started from.
+    pub start: Board,
+    /// Every move played, in order.
+    pub moves: Vec<Move>,
+    /// The position the game ended in.
+    pub final_board: Board,
+    /// How the game ended.
+    pub outcome: Outcome,
+}
+
+/// Play a game between two weight vectors, starting from `start`.
+///
+/// Both sides pick their moves with `get_best_next_move_cp`, which searches
+/// the root moves in order and keeps the first of equally scored moves.
+/// The same engines, start position and config therefore always produce
+/// the same game, which makes the record usable as a regression golden.
+pub fn play_game(
+    start: Board,
+    white: Option<[f64; 6]>,
+    black: Option<[f64; 6]>,
+    config: &MatchConfig,
+) -> GameRecord {
+    play_game_with(start, config, |board| {
+        let engine = match board.get_turn_color() {
+            Color::White => white,
+            Color::Black => black,
+        };
+        let (m, _, score) = board.get_best_next_move_cp(config.depth, engine);
+        (m, score)
+    })
+}
+
+/// Play a game starting from `start`, asking `choose` for each move.
+///
+/// `choose` returns the move for the player to move along with the score
+/// its search gives that move, in centipawns for that player. The scores
+/// are only used by the blunder resignation policy. `config.depth` is
+/// ignored, since `choose` decides how to search.
+pub fn play_game_with<F>(start: Board, config: &MatchConfig, mut choose: F) -> GameRecord
+where
+    F: FnMut(&Board) -> (Move, i32),
+{
+    let mut board = start;
+    let mut history = PositionHistory::default();
+    let mut moves = Vec::new();
+    // indexed by color, white first
+    let mut last_scores: [Option<i32>; 2] = [None, None];
+    let mut blunders = [0; 2];
+
+    let outcome = loop {
+        if moves.len() >= config.max_plies {
+            break Outcome::Unfinished;
+        }
+
+        let color = board.get_turn_color();
+        let side = match color {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+        if let Some(policy) = config.blunder_resign {
+            if blunders[side] >= policy.max_blunders {
+                moves.push(Move::Resign);
+                break Outcome::Victory(!color);
+            }
+        }
+
+        let (m, score) = choose(&board);
+        moves.push(m);
+        if let (Some(policy), Some(last_score)) = (config.blunder_resign, last_scores[side]) {
+            if last_score - score > policy.drop_cp {
+                blunders[side] += 1;
+            }
+        }
+        last_scores[side] = Some(score);
+
+        match board.play_move_with(m, &mut history) {
+            GameResult::Continuing(next_board) => board = next_board,
+            GameResult::Victory(winner) => break Outcome::Victory(winner),
+            GameResult::Stalemate => break Outcome::Draw,
+            // the search only plays moves it generated, so this means the
+            // move generator and the move validator disagree
+            GameResult::IllegalMove(m) => panic!("engine played illegal move {}", m),
+        }
+    };
+
+    GameRecord {
+        start,
+        moves,
+        final_board: board,
+        outcome,
+    }
+}
+
+/// The result of a match between two engines, from the first engine's
+/// point of view. Unfinished games count as draws.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchReport {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchReport {
+    /// The number of games played.
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The fraction of the points the first engine scored, counting a draw
+    /// as half a point.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// The estimated ELO difference between the first engine and the second.
+    /// This is infinite when one engine won every game.
+    pub fn elo_difference(&self) -> f64 {
+        elo_from_score(self.score())
+    }
+
+    /// A 95% confidence interval around `elo_difference`, lowest first.
+    pub fn elo_confidence_interval(&self) -> (f64, f64) {
+        let games = self.games() as f64;
+        let score = self.score();
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games;
+        let margin = 1.96 * (variance / games).sqrt();
+        (
+            elo_from_score((score - margin).max(0.0)),
+            elo_from_score((score + margin).min(1.0)),
+        )
+    }
+
+    /// The likelihood of superiority: how likely it is that the first
+    /// engine is actually the stronger one, given the decisive games.
+    pub fn likelihood_of_superiority(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        let z = (self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt();
+        0.5 * (1.0 + erf(z))
+    }
+}
+
+/// Play `games` pairs of games between two engines from the starting
+/// position, with each engine playing white once in every pair.
+///
+/// Engines choose moves the same way as in `play_game_with`. Deterministic
+/// engines play the same pair of games every time, so engines that should
+/// see different games need their own source of variety, like an `Rng`.
+pub fn compare_engines<A, B>(mut a: A, mut b: B, games: usize, config: &MatchConfig) -> MatchReport
+where
+    A: FnMut(&Board) -> (Move, i32),
+    B: FnMut(&Board) -> (Move, i32),
+{
+    let mut report = MatchReport::default();
+    for game in 0..games * 2 {
+        let a_color = if game % 2 == 0 { Color::White } else { Color::Black };
+        let record = play_game_with(Board::default(), config, |board| {
+            if board.get_turn_color() == a_color {
+                a(board)
+            } else {
+                b(board)
+            }
+        });
+        match record.outcome {
+            Outcome::Victory(winner) if winner == a_color => report.wins += 1,
+            Outcome::Victory(_) => report.losses += 1,
+            Outcome::Draw | Outcome::Unfinished => report.draws += 1,
+        }
+    }
+    report
+}
+
+/// What a sequential probability ratio test has concluded so far.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtVerdict {
+    /// The first engine is no more than `elo0` stronger. Stop the match.
+    AcceptH0,
+    /// The first engine is at least `elo1` stronger. Stop the match.
+    AcceptH1,
+    /// The games so far aren't conclusive. Keep playing.
+    Continue,
+}
+
+/// A sequential probability ratio test on whether one engine is stronger
+/// than another, fed one game at a time.
+///
+/// The test weighs the hypothesis that the first engine is `elo0` stronger
+/// (H0) against the hypothesis that it is `elo1` stronger (H1). `alpha` is
+/// the chance of accepting H1 when H0 is true, `beta` the chance of
+/// accepting H0 when H1 is true. The log-likelihood ratio uses the usual
+/// normal approximation of the game scores.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    elo0: f64,
+    elo1: f64,
+    lower: f64,
+    upper: f64,
+    report: MatchReport,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Self {
+        Self {
+            elo0,
+            elo1,
+            lower: (beta / (1.0 - alpha)).ln(),
+            upper: ((1.0 - beta) / alpha).ln(),
+            report: MatchReport::default(),
+        }
+    }
+
+    /// Record the result of a game, scored for the first engine as 1.0 for
+    /// a win, 0.5 for a draw and 0.0 for a loss.
+    pub fn update(&mut self, score: f64) -> SprtVerdict {
+        if score > 0.75 {
+            self.report.wins += 1;
+        } else if score < 0.25 {
+            self.report.losses += 1;
+        } else {
+            self.report.draws += 1;
+        }
+        self.verdict()
+    }
+
+    /// The verdict for the games recorded so far.
+    pub fn verdict(&self) -> SprtVerdict {
+        let llr = self.llr();
+        if llr >= self.upper {
+            SprtVerdict::AcceptH1
+        } else if llr <= self.lower {
+            SprtVerdict::AcceptH0
+        } else {
+            SprtVerdict::Continue
+        }
+    }
+
+    /// The log-likelihood ratio of H1 against H0 for the games so far.
+    pub fn llr(&self) -> f64 {
+        let games = self.report.games() as f64;
+        if games == 0.0 {
+            return 0.0;
+        }
+        let score = self.report.score();
+        let variance = (self.report.wins as f64 * (1.0 - score).powi(2)
+            + self.report.draws as f64 * (0.5 - score).powi(2)
+            + self.report.losses as f64 * score.powi(2))
+            / games;
+        // every game had the same result, so there's nothing to go on yet
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let score0 = score_from_elo(self.elo0);
+        let score1 = score_from_elo(self.elo1);
+        games * (score1 - score0) * (2.0 * score - score0 - score1) / (2.0 * variance)
+    }
+
+    /// The results recorded so far.
+    pub fn report(&self) -> MatchReport {
+        self.report
+    }
+}
+
+// the score expected from an ELO difference of `elo`
+fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// the ELO difference expected to produce a score of `score`
+fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+// Abramowitz and Stegun 7.1.26, accurate to about 1e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly = t * (0.254_829_592
+        + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let y = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 {
+        -y
+    } else {
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use alloc::string::String;
+
+    fn move_list(record: &GameRecord) -> String {
+        record
+            .moves
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    #[test]
+    fn test_golden_game_from_start() {
+        let config = MatchConfig {
+            depth: 1,
+            max_plies: 12,
+            ..MatchConfig::default()
+        };
+        let white = Some([1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let black = Some([1.0, 1.0, 0.0, 1.0, 0.0, 0.0]);
+        let record = play_game(Board::default(), white, black, &config);
+
+        assert_eq!(record.outcome, Outcome::Unfinished);
+        assert_eq!(
+            move_list(&record),
+            "b1 to c3, e7 to e5, g1 to f3, d8 to f6, f3 to g1, d7 to d5, \
+             c3 to d5, f6 to h4, d5 to c7, e8 to d7, c7 to a8, d7 to d8"
+        );
+        assert_eq!(record.final_board.fen_placement(), "Nnbk1bnr/pp3ppp/8/4p3/7q/8/PPPPPPPP/R1BQKBNR");
+        // the same game again, move for move
+        assert_eq!(play_game(Board::default(), white, black, &config), record);
+    }
+
+    #[test]
+    fn test_golden_game_from_endgame() {
+        let start = BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::Rook(WHITE, A1))
+            .piece(Piece::Pawn(WHITE, F2))
+            .piece(Piece::King(BLACK, E5))
+            .piece(Piece::Knight(BLACK, C6))
+            .build();
+        let config = MatchConfig {
+            depth: 2,
+            max_plies: 16,
+            ..MatchConfig::default()
+        };
+        let record = play_game(start, None, Some([1.0, 0.0, 1.0, 0.0, 1.0, 0.0]), &config);
+
+        assert_eq!(
+            move_list(&record),
+            "e1 to f1, e5 to f6, a1 to a6, f6 to f7, a6 to c6, f7 to e7, \
+             c6 to c7, e7 to d8, c7 to c1, d8 to e8, c1 to c7, e8 to d8, \
+             c7 to c1, d8 to e8, c1 to c7, e8 to d8"
+        );
+        // black's last king move reaches the same position for the third
+        // time, so the board is left as it was before it
+        assert_eq!(record.final_board.fen_placement(), "4k3/2R5/8/8/8/8/5P2/5K2");
+        assert_eq!(record.outcome, Outcome::Draw);
+    }
+
+    #[test]
+    fn test_blunder_resignation() {
+        // white always plays the move its own search likes least
+        let choose = |board: &Board| {
+            let color = board.get_turn_color();
+            if color == BLACK {
+                let (m, _, score) = board.get_best_next_move_cp(1, None);
+                return (m, score);
+            }
+            board
+                .get_legal_moves()
+                .into_iter()
+                .map(|m| {
+                    let score = board.apply_eval_move(m).minimax_cp(
+                        1,
+                        -MATE_SCORE_CP - 1,
+                        MATE_SCORE_CP + 1,
+                        false,
+                        color,
+                        &mut 0,
+                        None,
+                        1,
+                    );
+                    (m, score)
+                })
+                .min_by_key(|(_, score)| *score)
+                .unwrap()
+        };
+        let start = BoardBuilder::default()
+            .piece(Piece::King(WHITE, E1))
+            .piece(Piece::Queen(WHITE, D1))
+            .piece(Piece::Rook(WHITE, A1))
+            .piece(Piece::Knight(WHITE, G1))
+            .piece(Piece::Bishop(WHITE, F1))
+            .piece(Piece::King(BLACK, E8))
+            .piece(Piece::Queen(BLACK, D8))
+            .piece(Piece::Rook(BLACK, H8))
+            .row(Piece::Pawn(BLACK, A7))
+            .build();
+        let mut config = MatchConfig {
+            max_plies: 40,
+            ..MatchConfig::default()
+        };
+
+        let record = play_game_with(start, &config, choose);
+        assert_ne!(record.moves.last(), Some(&Move::Resign));
+
+        config.blunder_resign = Some(BlunderPolicy {
+            drop_cp: 200,
+            max_blunders: 2,
+        });
+        let record = play_game_with(start, &config, choose);
+        assert_eq!(record.outcome, Outcome::Victory(BLACK));
+        assert_eq!(record.moves.last(), Some(&Move::Resign));
+        assert!(record.moves.len() < 40);
+    }
+
+    #[test]
+    fn test_match_report() {
+        let report = MatchReport {
+            wins: 30,
+            draws: 40,
+            losses: 30,
+        };
+        assert_eq!(report.score(), 0.5);
+        assert!(report.elo_difference().abs() < 1e-9);
+        assert!((report.likelihood_of_superiority() - 0.5).abs() < 1e-9);
+        let (low, high) = report.elo_confidence_interval();
+        assert!(low < 0.0 && high > 0.0);
+        assert!((low + high).abs() < 1e-9);
+
+        let report = MatchReport {
+            wins: 60,
+            draws: 20,
+            losses: 20,
+        };
+        assert_eq!(report.score(), 0.7);
+        assert!((report.elo_difference() - 147.2).abs() < 0.1);
+        let (low, high) = report.elo_confidence_interval();
+        assert!(0.0 < low && low < report.elo_difference() && report.elo_difference() < high);
+        assert!(report.likelihood_of_superiority() > 0.99);
+    }
+
+    #[test]
+    fn test_compare_engines() {
+        let config = MatchConfig {
+            max_plies: 120,
+            ..MatchConfig::default()
+        };
+        let material = |board: &Board| {
+            let (m, _, score) = board.get_best_next_move_cp(1, None);
+            (m, score)
+        };
+        let mut rng = Rng::new(5);
+        let random = move |board: &Board| {
+            let moves = board.get_legal_moves();
+            (moves[rng.below(moves.len())], 0)
+        };
+
+        let report = compare_engines(material, random, 2, &config);
+        assert_eq!(report.games(), 4);
+        assert_eq!(report.losses, 0);
+        assert!(report.wins >= 3, "{:?}", report);
+        assert!(report.elo_difference() > 200.0);
+    }
+
+    #[test]
+    fn test_sprt() {
+        let mut sprt = Sprt::new(0.0, 10.0, 0.05, 0.05);
+        assert_eq!(sprt.verdict(), SprtVerdict::Continue);
+        for _ in 0..60 {
+            sprt.update(1.0);
+        }
+        for _ in 0..20 {
+            sprt.update(0.5);
+        }
+        for _ in 0..20 {
+            sprt.update(0.0);
+        }
+        assert!((sprt.llr() - 1.733_713).abs() < 1e-6);
+        assert_eq!(sprt.verdict(), SprtVerdict::Continue);
+        assert_eq!(
+            sprt.report(),
+            MatchReport {
+                wins: 60,
+                draws: 20,
+                losses: 20,
+            }
+        );
+
+        let mut verdict = SprtVerdict::Continue;
+        let mut games = 0;
+        while verdict == SprtVerdict::Continue {
+            verdict = sprt.update([1.0, 0.5][games % 2]);
+            games += 1;
+        }
+        assert_eq!(verdict, SprtVerdict::AcceptH1);
+        assert!(sprt.llr() >= (19.0f64).ln());
+
+        // an even match rejects a 10 ELO improvement
+        let mut sprt = Sprt::new(0.0, 10.0, 0.05, 0.05);
+        let mut games = 0;
+        while sprt.update([1.0, 0.5, 0.0, 0.5][games % 4]) == SprtVerdict::Continue {
+            games += 1;
+        }
+        assert_eq!(sprt.verdict(), SprtVerdict::AcceptH0);
+        assert!(sprt.llr() <= -(19.0f64).ln());
+    }
+}
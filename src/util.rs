@@ -6,17 +6,9 @@ use crate::piece::Piece;
 use crate::position::Position;
 use crate::{Color, Evaluate, Move};
 
-// generate FEN
-// cannot calculate halfmove or fullmove based on board state,
-// so they must be provided
-pub fn format_fen(
-    board: &Board,
-    halfmove_clock: u8,
-    fullmove_number: u8,
-) -> Result<String, String> {
+// generate only the piece placement field of FEN
+pub fn format_fen_placement(board: &Board) -> String {
     let mut fen: Vec<String> = vec![];
-
-    // placement
     let mut skip: i32 = 0;
     for row in (0..8).rev() {
         for col in 0..8 {
@@ -54,6 +46,14 @@ pub fn format_fen(
             fen.push("/".to_string());
         }
     }
+    fen.concat()
+}
+
+// generate FEN
+// the move counters come from the board, which keeps them as moves are
+// played, so this is always a full six-field FEN
+pub fn format_fen(board: &Board) -> String {
+    let mut fen: Vec<String> = vec![format_fen_placement(board)];
 
     // active color
     fen.push(" ".to_string());
@@ -102,48 +102,45 @@ pub fn format_fen(
 
     // half move
     fen.push(" ".to_string());
-    let halfmove_clock = halfmove_clock.to_string();
-    fen.push(halfmove_clock);
+    fen.push(board.halfmove_clock().to_string());
 
     // full move
     fen.push(" ".to_string());
-    let fullmove_number = fullmove_number.to_string();
-    fen.push(fullmove_number);
+    fen.push(board.fullmove_number().to_string());
 
-    Ok(fen.join(""))
+    fen.join("")
 }
 
 // parse Forsyth–Edwards Notation (FEN) board state
 // more direct than replaying every move from PGN
 pub fn parse_fen(fen: &str) -> Result<Board, String> {
-    let mut parts = fen.split_ascii_whitespace();
-    // fen has six parts
-    let placement = parts.next();
-    let active_color = parts.next();
-    let castling = parts.next();
-    let en_passant = parts.next();
-    let _halfmove_clock = parts.next();
-    let _fullmove_number = parts.next();
-    // make sure all parts present
-    if placement.is_none()
-        || active_color.is_none()
-        || castling.is_none()
-        || en_passant.is_none()
-        // ignore half move and full move for now
-        // || halfmove_clock.is_none()
-        // || fullmove_number.is_none()
-        // extra part
-        || parts.next().is_some()
-    {
-        return Err(String::from("wrong number of spaces"));
-    }
+    let parts: Vec<&str> = fen.split_ascii_whitespace().collect();
+    // fen has six parts, but the move counters are commonly left off.
+    // missing counters default to halfmove clock 0 and fullmove number 1
+    let (placement, active_color, castling, en_passant, halfmove_clock, fullmove_number) =
+        match parts.as_slice() {
+            [placement, active_color, castling, en_passant] => {
+                (placement, active_color, castling, en_passant, "0", "1")
+            }
+            [placement, active_color, castling, en_passant, halfmove_clock, fullmove_number] => (
+                placement,
+                active_color,
+                castling,
+                en_passant,
+                *halfmove_clock,
+                *fullmove_number,
+            ),
+            _ => {
+                return Err(String::from("wrong number of spaces"));
+            }
+        };
 
     let mut builder = BoardBuilder::default();
 
     // parse placement (from white's perspective)
     let mut row: i32 = 7;
     let mut col: i32 = 0;
-    for c in placement.unwrap().chars() {
+    for c in placement.chars() {
         if (col > 7 && c != '/') || row < 0 {
             return Err(String::from("too many pieces"));
         }
@@ -169,10 +166,12 @@ pub fn parse_fen(fen: &str) -> Result<Board, String> {
                 builder = builder.piece(piece);
                 col += 1;
             }
-            x if x.is_numeric() => {
+            '1'..='8' => {
                 // skip squares
-                let skip = x.to_digit(10).unwrap();
-                col += skip as i32;
+                col += c.to_digit(10).unwrap() as i32;
+                if col > 8 {
+                    return Err(String::from("too many pieces"));
+                }
             }
             '/' => {
                 if col != 8 {
@@ -181,22 +180,25 @@ pub fn parse_fen(fen: &str) -> Result<Board, String> {
                 col = 0;
                 row -= 1;
             }
-            _ => {}
+            _ => {
+                return Err(format!("unexpected character `{}` in placement", c));
+            }
         };
     }
-    if col != 8 && row != 0 {
+    if col != 8 || row != 0 {
         return Err(String::from("incomplete position"));
     }
 
-    builder = builder.set_turn(match active_color.unwrap() {
+    let turn = match *active_color {
         "b" => Color::Black,
         "w" => Color::White,
         _ => {
             return Err(String::from("invalid active color"));
         }
-    });
+    };
+    builder = builder.set_turn(turn);
 
-    match castling.unwrap() {
+    match *castling {
         "-" => {}
         castling => {
             for c in castling.chars() {
@@ -219,16 +221,33 @@ pub fn parse_fen(fen: &str) -> Result<Board, String> {
         }
     };
 
-    builder = builder.set_en_passant(match en_passant.unwrap() {
+    builder = builder.set_en_passant(match *en_passant {
         "-" => None,
         some => match Position::pgn(some) {
-            Ok(position) => Some(position),
+            // the square a pawn of the player who just moved skipped over
+            Ok(position) if position.get_row() == if turn == Color::White { 5 } else { 2 } => {
+                Some(position)
+            }
             _ => {
                 return Err(String::from("invalid en passant"));
             }
         },
     });
 
+    builder = builder.set_halfmove_clock(match halfmove_clock.parse() {
+        Ok(halfmove_clock) => halfmove_clock,
+        _ => {
+            return Err(String::from("invalid halfmove clock"));
+        }
+    });
+
+    builder = builder.set_fullmove_number(match fullmove_number.parse() {
+        Ok(fullmove_number) if fullmove_number > 0 => fullmove_number,
+        _ => {
+            return Err(String::from("invalid fullmove number"));
+        }
+    });
+
     Ok(builder.build())
 }
 
@@ -362,6 +381,80 @@ pub fn parse_san_move(board: &Board, move_str: &str) -> Result<Move, String> {
     }
 }
 
+// parse the six evaluation weights of an engine
+//
+// weights are written as six comma separated numbers, like "1.5,0,0.25,0,-1,0".
+// engines stored before weights could be fractional are six digits with no
+// separators, like "100000", and still parse the same way.
+pub fn parse_engine(engine: &str) -> Result<[f64; 6], String> {
+    let weights: Vec<&str> = if engine.contains(',') {
+        engine.split(',').map(|weight| weight.trim()).collect()
+    } else {
+        engine
+            .char_indices()
+            .map(|(i, c)| &engine[i..i + c.len_utf8()])
+            .collect()
+    };
+    if weights.len() != 6 {
+        return Err(format!("engine `{}` needs 6 weights, not {}", engine, weights.len()));
+    }
+
+    let mut result = [0.0; 6];
+    for (i, weight) in weights.iter().enumerate() {
+        result[i] = match weight.parse::<f64>() {
+            Ok(value) if value.is_finite() => value,
+            _ => return Err(format!("engine `{}` has an invalid weight `{}`", engine, weight)),
+        };
+    }
+    Ok(result)
+}
+
+// write the six evaluation weights of an engine so parse_engine can read them back
+//
+// engines whose weights are all whole numbers from 0 to 9 keep the old six digit
+// form, so they still match the engines already stored that way.
+pub fn format_engine(engine: [f64; 6]) -> String {
+    if engine.iter().all(|weight| weight.fract() == 0.0 && (0.0..=9.0).contains(weight)) {
+        engine.iter().map(|weight| (*weight as u8).to_string()).collect()
+    } else {
+        engine
+            .iter()
+            .map(|weight| weight.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+// seedable random number generator (xorshift64*)
+//
+// works without std, and the same seed always gives the same numbers,
+// so randomly generated games and positions can be reproduced
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on a state of zero
+        Rng {
+            state: if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // random index in 0..len, len must not be zero
+    pub fn below(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::string::{String, ToString};
@@ -376,17 +469,18 @@ mod tests {
         let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
         let board = Board::default();
-        assert_eq!(format_fen(&board, 0, 1).unwrap(), String::from(start));
+        assert_eq!(format_fen(&board), String::from(start));
+        assert_eq!(board.fen(), start);
 
         let board = parse_fen(start).unwrap();
-        assert_eq!(format_fen(&board, 0, 1).unwrap(), String::from(start));
+        assert_eq!(format_fen(&board), String::from(start));
 
         let board = match board.play_move(Move::Piece(E2, E4)) {
             GameResult::Continuing(board) => board,
             _ => panic!("e4 failed"),
         };
         assert_eq!(
-            format_fen(&board, 0, 1).unwrap(),
+            format_fen(&board),
             "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_string()
         );
 
@@ -395,7 +489,7 @@ mod tests {
             _ => panic!("c5 failed"),
         };
         assert_eq!(
-            format_fen(&board, 0, 2).unwrap(),
+            format_fen(&board),
             "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2".to_string()
         );
 
@@ -404,11 +498,123 @@ mod tests {
             _ => panic!("Nf3 failed"),
         };
         assert_eq!(
-            format_fen(&board, 1, 2).unwrap(),
+            format_fen(&board),
             "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2".to_string()
         )
     }
 
+    #[test]
+    fn test_fen_move_counters() {
+        let board = parse_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3").unwrap();
+        assert_eq!(board.halfmove_clock(), 0);
+        assert_eq!(board.fullmove_number(), 1);
+        assert_eq!(board.get_en_passant(), Some(E3));
+
+        let board = parse_fen("8/5kp1/8/8/8/8/3K2P1/8 w - - 12 57").unwrap();
+        assert_eq!(board.halfmove_clock(), 12);
+        assert_eq!(board.fullmove_number(), 57);
+        let board = match board.play_move(Move::Piece(D2, D3)) {
+            GameResult::Continuing(board) => board,
+            _ => panic!("Kd3 failed"),
+        };
+        assert_eq!(board.halfmove_clock(), 13);
+        assert_eq!(board.fullmove_number(), 57);
+        let board = match board.play_move(Move::Piece(F7, F6)) {
+            GameResult::Continuing(board) => board,
+            _ => panic!("Kf6 failed"),
+        };
+        assert_eq!(board.fullmove_number(), 58);
+
+        // both counters or neither
+        assert!(parse_fen("8/5k2/8/8/8/8/3K4/8 w - - 12").is_err());
+        assert!(parse_fen("8/5k2/8/8/8/8/3K4/8 w -").is_err());
+        assert!(parse_fen("8/5k2/8/8/8/8/3K4/8 w - - 12 57 1").is_err());
+        assert!(parse_fen("8/5k2/8/8/8/8/3K4/8 w - - x 57").is_err());
+        assert!(parse_fen("8/5k2/8/8/8/8/3K4/8 w - - 0 0").is_err());
+
+        assert_eq!(
+            Board::from_fen("8/5k2/8/8/8/8/3K4/8 w - -"),
+            parse_fen("8/5k2/8/8/8/8/3K4/8 w - - 0 1")
+        );
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+            "rnbqkbnr/pppp1ppp/8/8/3pP3/8/PPP2PPP/RNBQKBNR b Kq e3 0 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Qk - 7 40",
+            "8/5kp1/8/8/8/8/3K2P1/8 b - - 99 300",
+        ];
+        for fen in &fens {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.fen(), *fen);
+            assert_eq!(Board::from_fen(&board.fen()), Ok(board), "{}", fen);
+        }
+
+        // boards reached by playing moves keep their counters too
+        let board = Board::from_fen(fens[1]).unwrap();
+        let board = match board.play_move(Move::Piece(E4, E5)) {
+            GameResult::Continuing(board) => board,
+            _ => panic!("e5 failed"),
+        };
+        let board = match board.play_move(Move::Piece(D7, D5)) {
+            GameResult::Continuing(board) => board,
+            _ => panic!("d5 failed"),
+        };
+        assert_eq!(board.fen(), "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3");
+        assert_eq!(Board::from_fen(&board.fen()), Ok(board));
+    }
+
+    #[test]
+    fn test_fen_errors() {
+        let malformed = [
+            "",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR/8 w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/72/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN? w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQxq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq e6 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - -1 1",
+        ];
+        for fen in &malformed {
+            assert!(Board::from_fen(fen).is_err(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_engine_format() {
+        let engines = [
+            [1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            [9.0, 2.0, 0.0, 7.0, 1.0, 3.0],
+            [1.5, 0.0, 0.25, 0.0, -1.0, 0.0],
+            [-2.0, 10.0, 0.1, 0.0, 0.0, 3.0],
+        ];
+        for engine in &engines {
+            assert_eq!(parse_engine(&format_engine(*engine)), Ok(*engine));
+        }
+
+        // single digit weights keep the old format
+        assert_eq!(format_engine(engines[1]), "920713");
+        assert_eq!(parse_engine("920713"), Ok(engines[1]));
+        assert_eq!(format_engine(engines[2]), "1.5,0,0.25,0,-1,0");
+        assert_eq!(parse_engine("1.5, 0, .25, 0, -1, 0"), Ok(engines[2]));
+
+        assert!(parse_engine("12345").is_err());
+        assert!(parse_engine("1,2,3,4,5,6,7").is_err());
+        assert!(parse_engine("1,2,x,4,5,6").is_err());
+        assert!(parse_engine("1,2,NaN,4,5,6").is_err());
+        assert!(parse_engine("").is_err());
+    }
+
     #[test]
     fn test_parse_san_move() {
         let mut board = Board::default();
@@ -0,0 +1,76 @@
+use crate::{Color, Piece};
+
+/// Random keys for every piece on every square, indexed by
+/// `piece_index` and then `row * 8 + col`.
+pub(crate) const PIECE_KEYS: [[u64; 64]; 12] = {
+    let mut keys = [[0; 64]; 12];
+    let mut state = SEED;
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            state = next_key(state);
+            keys[piece][square] = state;
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+};
+
+/// Keys for white kingside, white queenside, black kingside and black
+/// queenside castling rights.
+pub(crate) const CASTLING_KEYS: [u64; 4] = {
+    let mut keys = [0; 4];
+    let mut state = next_key(PIECE_KEYS[11][63]);
+    let mut i = 0;
+    while i < 4 {
+        keys[i] = state;
+        state = next_key(state);
+        i += 1;
+    }
+    keys
+};
+
+/// Keys for the file of the en passant square.
+pub(crate) const EN_PASSANT_KEYS: [u64; 8] = {
+    let mut keys = [0; 8];
+    let mut state = next_key(CASTLING_KEYS[3]);
+    let mut i = 0;
+    while i < 8 {
+        keys[i] = state;
+        state = next_key(state);
+        i += 1;
+    }
+    keys
+};
+
+/// The key toggled when it is Black's turn.
+pub(crate) const BLACK_TO_MOVE_KEY: u64 = next_key(EN_PASSANT_KEYS[7]);
+
+const SEED: u64 = 0x2d35_8dcc_aa6c_78a5;
+
+/// Get the next key in a fixed splitmix64 sequence, so the keys are
+/// the same on every build.
+const fn next_key(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Get the index of a piece's keys in `PIECE_KEYS`.
+pub(crate) fn piece_index(piece: Piece) -> usize {
+    let kind = match piece {
+        Piece::King(..) => 0,
+        Piece::Queen(..) => 1,
+        Piece::Rook(..) => 2,
+        Piece::Bishop(..) => 3,
+        Piece::Knight(..) => 4,
+        Piece::Pawn(..) => 5,
+    };
+    match piece.get_color() {
+        Color::White => kind,
+        Color::Black => kind + 6,
+    }
+}
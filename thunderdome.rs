@@ -13,27 +13,25 @@ use std::time::{Duration, Instant};
 
 fn get_cpu_move(b: &Board, w_engine: Option<[f64; 6]>, b_engine: Option<[f64; 6]>) -> Move {
     let mut depth = 4;
-    let min_time = 6.5; //seconds
+    let budget = Duration::from_secs(10);
     let max_depth = 15;
-
-    let mut start = Instant::now();
-    let (mut m, mut count, _) = if b.get_turn_color() == Color::White {
-        b.get_best_next_move(depth, w_engine)
-    } else {
-        b.get_best_next_move(depth, b_engine)
-    };
-    while start.elapsed().as_secs_f64() < min_time && count < 10000 && depth < max_depth {
-        start = Instant::now();
+    let engine = if b.get_turn_color() == Color::White { w_engine } else { b_engine };
+
+    let start = Instant::now();
+    let mut iteration_times = vec![];
+    let (mut m, mut count, _) = b.get_best_next_move(depth, engine);
+    iteration_times.push(start.elapsed());
+    // only go deeper when the next depth is expected to finish in time
+    while depth < max_depth && next_depth_fits(&iteration_times, budget.saturating_sub(start.elapsed())) {
         depth += 1;
-        println!(" - Redoing with depth {}, previous search was {} nodes under 10s", depth, count);
-        
-        (m, count, _) = if b.get_turn_color() == Color::White {
-            b.get_best_next_move(depth, w_engine)
-        } else {
-            b.get_best_next_move(depth, b_engine)
-        };
+        println!(" - Redoing with depth {}, previous search was {} nodes", depth, count);
+
+        let depth_start = Instant::now();
+        (m, count, _) = b.get_best_next_move(depth, engine);
+        iteration_times.push(depth_start.elapsed());
     }
-    let nodes_per_sec = ((count as f64) / (start.elapsed().as_secs_f64())).round();
+    let last_time = iteration_times[iteration_times.len() - 1];
+    let nodes_per_sec = ((count as f64) / last_time.as_secs_f64()).round();
     print!("CPU evaluated {} moves before choosing to ", count);
     match m {
         Move::Piece(from, to) | Move::Promotion(from, to, _) => {
@@ -65,52 +63,21 @@ fn get_cpu_move(b: &Board, w_engine: Option<[f64; 6]>, b_engine: Option<[f64; 6]
 }
 
 fn engine_array(engine_str: &str) -> [f64; 6] {
-    let mut result = [0.0; 6];
-    for (i, c) in engine_str.chars().enumerate() {
-        let num = c.to_digit(10).unwrap() as f64;
-        result[i] = num;
-    }
-    result
+    parse_engine(engine_str).unwrap()
 }
 fn engine_str(engine_arr: [f64;6]) -> String {
-    let mut result = "".to_string();
-    for i in engine_arr {
-        result = format!("{result}{i}");
-    }
-    result
+    format_engine(engine_arr)
 }
     
-fn calculate_elo(player1_elo: f64, player2_elo: f64, result: GameResult) -> (f64, f64) {
-    // Constants for the ELO system
-    const K_FACTOR: f64 = 32.0;
-    const ELO_DIFFERENCE_LIMIT: f64 = 400.0;
-    const WIN_PROBABILITY_CONSTANT: f64 = 10.0;
-
-    // Calculate the expected win probability for player 1
-    let elo_difference = (player2_elo - player1_elo) / ELO_DIFFERENCE_LIMIT;
-    let expected_win_probability = 1.0 / (1.0 + WIN_PROBABILITY_CONSTANT.powf(elo_difference));
-
-    // Calculate the actual result
-    let actual_result = match result {
-        GameResult::Stalemate => 0.5,
-        GameResult::Victory(_) => 1.0,
-        GameResult::Continuing(_) => {return (player1_elo, player2_elo)},
-        GameResult::IllegalMove(_) => {return (player1_elo, player2_elo)},
-    };
-
-    // Calculate the new ELO ratings for both players
-    let player1_new_elo = player1_elo + K_FACTOR * (actual_result - expected_win_probability);
-    let player2_new_elo = player2_elo + K_FACTOR * (expected_win_probability - actual_result);
-
-    (player1_new_elo, player2_new_elo)
-}
-
 #[tokio::main]
 async fn main() -> Result<(), String> {    
     println!("THUNDERDOME!");
     dotenv().ok();
 
     let uri = env::var("MONGO_CONNECTION_STRING").unwrap();
+    // what the side ahead in material scores for a draw, to try out
+    // penalizing draws from winning positions; the other side gets the rest
+    let draw_score: f64 = env::var("DRAW_SCORE").ok().and_then(|s| s.parse().ok()).unwrap_or(0.5);
     let mut client_options =tokio::task::spawn_blocking(move || {
         ClientOptions::parse(&uri).unwrap()
     }).await.unwrap();
@@ -157,6 +124,7 @@ async fn main() -> Result<(), String> {
         }
 
         let mut b = Board::default();
+        let mut positions = PositionHistory::default();
 
         let pipeline = vec![
                 doc! {
@@ -183,18 +151,19 @@ async fn main() -> Result<(), String> {
         loop {
             let m = get_cpu_move(&b, Some(w_engine), Some(b_engine));          
 
-            match b.play_move(m) {
+            match b.play_move_with(m, &mut positions) {
                 GameResult::Continuing(next_board) => {
                     b = next_board;
                     println!("{}", b.fen());
+                    let components = b.eval_components(b.get_turn_color());
                     println!("{}: {} {} {} {} {} {}", 
                         b.get_turn_color(),
-                        b.value_for(b.get_turn_color()),
-                        b.mobility_value_for(b.get_turn_color()),
-                        b.naive_value_for(b.get_turn_color()),
-                        b.control_value_for(b.get_turn_color()),
-                        b.closest_value_for(b.get_turn_color()),
-                        b.trade_value_for(b.get_turn_color())
+                        components[0],
+                        components[1],
+                        components[2],
+                        components[3],
+                        components[4],
+                        components[5]
                     );
 
                     let next_move: String;
@@ -239,12 +208,7 @@ async fn main() -> Result<(), String> {
                     let black_engine_bson = engine_col.find_one(doc! {"engine":engine_str(b_engine)}, None).await.ok().unwrap().unwrap();
                     let white_engine_bson = engine_col.find_one(doc! {"engine":engine_str(w_engine)}, None).await.ok().unwrap().unwrap();
 
-                    let elos: (f64, f64) = if winner == Color::White {
-                        calculate_elo(white_engine_bson.get_f64("elo").unwrap() as f64, black_engine_bson.get_f64("elo").unwrap() as f64, GameResult::Victory(winner))
-                    } else {
-                        let t = calculate_elo(black_engine_bson.get_f64("elo").unwrap(), white_engine_bson.get_f64("elo").unwrap(), GameResult::Victory(winner));
-                        (t.1, t.0)
-                    };                    
+                    let elos: (f64, f64) = update_elo(white_engine_bson.get_f64("elo").unwrap(), black_engine_bson.get_f64("elo").unwrap(), GameResult::Victory(winner), DEFAULT_K_FACTOR);
 
                     update = doc! {
                         "$set": Bson::from(doc! {
@@ -294,7 +258,13 @@ async fn main() -> Result<(), String> {
                     let black_engine_bson = engine_col.find_one(doc! {"engine":engine_str(b_engine)}, None).await.ok().unwrap().unwrap();
                     let white_engine_bson = engine_col.find_one(doc! {"engine":engine_str(w_engine)}, None).await.ok().unwrap().unwrap();
 
-                    let elos: (f64, f64) = calculate_elo(white_engine_bson.get_f64("elo").unwrap(), black_engine_bson.get_f64("elo").unwrap(), GameResult::Stalemate);
+                    let ahead = match b.get_material_advantage(Color::White) {
+                        0 => None,
+                        advantage if advantage > 0 => Some(Color::White),
+                        _ => Some(Color::Black),
+                    };
+                    let white_score = draw_score_for_white(ahead, draw_score);
+                    let elos: (f64, f64) = update_elo_with_score(white_engine_bson.get_f64("elo").unwrap(), black_engine_bson.get_f64("elo").unwrap(), white_score, DEFAULT_K_FACTOR);
                     update = doc! {
                         "$set": Bson::from(doc! {
                             "elo":elos.0,